@@ -0,0 +1,172 @@
+// Clock rate benchmark, run by `cargo bench --bench clock`
+//
+// Simulates a model of `N` counters, each a register, an adder, a mux and a
+// sign extender, and reports the achieved clocks per second, for full and
+// incremental evaluation. Every other counter holds its value.
+//
+// The baseline is the same model with the adders, muxes and sign extenders
+// reading their inputs and setting their outputs by id and field
+// (`get_input_val/set_out_val`), as all components did before inputs were
+// resolved to `sim_state` indexes.
+use serde::{Deserialize, Serialize};
+use std::{
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use syncrim::{
+    common::{
+        Component, ComponentStore, EvalMode, Id, Input, OutputType, Ports, Signal, SignedSignal,
+        Simulator,
+    },
+    components::*,
+};
+
+const N: usize = 1000;
+const DURATION: Duration = Duration::from_secs(3);
+
+// A combinatorial component of the baseline, looking up its inputs and
+// output by id and field
+#[derive(Serialize, Deserialize)]
+struct Lookup {
+    id: Id,
+    op: Op,
+    inputs: Vec<Input>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Op {
+    Add,
+    Mux,
+    Sext,
+}
+
+#[typetag::serde]
+impl Component for Lookup {
+    fn get_id_ports(&self) -> (Id, Ports) {
+        (
+            self.id.clone(),
+            Ports::new(
+                self.inputs.iter().collect(),
+                OutputType::Combinatorial,
+                vec!["out"],
+            ),
+        )
+    }
+
+    fn clock(&self, simulator: &mut Simulator) {
+        let input = |i: usize| simulator.get_input_val(&self.inputs[i]);
+        let value = match self.op {
+            Op::Add => (input(0) as u32).wrapping_add(input(1) as u32) as Signal,
+            Op::Mux => input(1 + input(0) as usize),
+            Op::Sext => ((input(0) << 48) as SignedSignal >> 48) as Signal & 0xffff_ffff,
+        };
+        simulator.set_out_val(&self.id, "out", value);
+    }
+}
+
+#[cfg(feature = "gui-vizia")]
+#[typetag::serde]
+impl syncrim::common::ViziaComponent for Lookup {}
+
+#[cfg(feature = "gui-egui")]
+#[typetag::serde]
+impl syncrim::common::EguiComponent for Lookup {}
+
+fn model(baseline: bool) -> ComponentStore {
+    let mut cs = ComponentStore {
+        store: vec![
+            Rc::new(Constant {
+                id: "one".into(),
+                pos: (0.0, 0.0),
+                value: 1,
+            }),
+            Rc::new(Constant {
+                id: "count".into(),
+                pos: (0.0, 0.0),
+                value: 1,
+            }),
+            Rc::new(Constant {
+                id: "hold".into(),
+                pos: (0.0, 0.0),
+                value: 0,
+            }),
+        ],
+    };
+    for i in 0..N {
+        let id = |name: &str| format!("{}{}", name, i);
+        cs.store.push(Rc::new(Register {
+            id: id("reg"),
+            pos: (0.0, 0.0),
+            r_in: Input::new(&id("sext"), "out"),
+        }));
+        let add = vec![Input::new("one", "out"), Input::new(&id("reg"), "out")];
+        let select = Input::new(["count", "hold"][i % 2], "out");
+        let m_in = vec![Input::new(&id("reg"), "out"), Input::new(&id("add"), "out")];
+        let sext_in = Input::new(&id("mux"), "out");
+        if baseline {
+            let lookup = |name: &str, op: Op, inputs: Vec<Input>| {
+                Rc::new(Lookup {
+                    id: id(name),
+                    op,
+                    inputs,
+                })
+            };
+            cs.store.push(lookup("add", Op::Add, add));
+            cs.store
+                .push(lookup("mux", Op::Mux, [vec![select], m_in].concat()));
+            cs.store.push(lookup("sext", Op::Sext, vec![sext_in]));
+            continue;
+        }
+        cs.store.push(Rc::new(Add {
+            id: id("add"),
+            pos: (0.0, 0.0),
+            a_in: add[0].clone(),
+            b_in: add[1].clone(),
+            width: 32,
+        }));
+        cs.store.push(Rc::new(Mux {
+            id: id("mux"),
+            pos: (0.0, 0.0),
+            select,
+            m_in,
+        }));
+        cs.store.push(Rc::new(Sext {
+            id: id("sext"),
+            pos: (0.0, 0.0),
+            sext_in,
+            in_size: 16,
+            out_size: 32,
+        }));
+    }
+    cs
+}
+
+fn bench(name: &str, baseline: bool, eval_mode: EvalMode) {
+    let mut clock = 0;
+    let mut simulator = Simulator::new(&model(baseline), &mut clock).unwrap();
+    // keep the history small, measuring evaluation only
+    simulator.set_history_depth(Some(1));
+    simulator.set_eval_mode(eval_mode);
+
+    let start = Instant::now();
+    while start.elapsed() < DURATION {
+        for _ in 0..100 {
+            simulator.clock(&mut clock);
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{}: {} components, {} clocks in {:.2} s, {:.0} clocks/s",
+        name,
+        simulator.ordered_components.len(),
+        clock - 1,
+        elapsed,
+        (clock - 1) as f64 / elapsed
+    );
+}
+
+fn main() {
+    bench("Baseline (lookup by id)", true, EvalMode::Full);
+    bench("Full", false, EvalMode::Full);
+    bench("Incremental", false, EvalMode::Incremental);
+}
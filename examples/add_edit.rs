@@ -7,7 +7,21 @@ use syncrim::{
 
 fn main() {
     fern_setup();
-    let cs = ComponentStore {
+    let cs = model();
+
+    let path = PathBuf::from("add.json");
+    cs.save_file(&path).unwrap();
+
+    #[cfg(feature = "gui-egui")]
+    syncrim::gui_egui::gui(&cs, &path).ok();
+
+    #[cfg(feature = "gui-vizia")]
+    syncrim::gui_vizia::gui(&cs, &path);
+}
+
+// the model of the example, also built by `tests/examples.rs`
+pub fn model() -> ComponentStore {
+    ComponentStore {
         store: vec![
             Rc::new(Add {
                 id: "add".to_string(),
@@ -15,6 +29,7 @@ fn main() {
                 a_in: Input::new("c1", "out"),
 
                 b_in: Input::new("c2", "out"),
+                width: 32,
             }),
             Rc::new(ProbeEdit::new("c1", (60.0, 100.0))),
             Rc::new(ProbeEdit::new("c2", (60.0, 140.0))),
@@ -42,14 +57,5 @@ fn main() {
                 input: Input::new("add", "out"),
             }),
         ],
-    };
-
-    let path = PathBuf::from("add.json");
-    cs.save_file(&path);
-
-    #[cfg(feature = "gui-egui")]
-    syncrim::gui_egui::gui(&cs, &path).ok();
-
-    #[cfg(feature = "gui-vizia")]
-    syncrim::gui_vizia::gui(&cs, &path);
+    }
 }
@@ -7,7 +7,21 @@ use syncrim::{
 
 fn main() {
     fern_setup();
-    let cs = ComponentStore {
+    let cs = model();
+
+    let path = PathBuf::from("mem.json");
+    cs.save_file(&path).unwrap();
+
+    #[cfg(feature = "gui-egui")]
+    syncrim::gui_egui::gui(&cs, &path).ok();
+
+    #[cfg(feature = "gui-vizia")]
+    syncrim::gui_vizia::gui(&cs, &path);
+}
+
+// the model of the example, also built by `tests/examples.rs`
+pub fn model() -> ComponentStore {
+    ComponentStore {
         store: vec![
             Rc::new(Mem {
                 id: "mem".to_string(),
@@ -104,14 +118,5 @@ fn main() {
                 input: Input::new("mem", "err"),
             }),
         ],
-    };
-
-    let path = PathBuf::from("mem.json");
-    cs.save_file(&path);
-
-    #[cfg(feature = "gui-egui")]
-    syncrim::gui_egui::gui(&cs, &path).ok();
-
-    #[cfg(feature = "gui-vizia")]
-    syncrim::gui_vizia::gui(&cs, &path);
+    }
 }
@@ -7,19 +7,10 @@ use syncrim::{
 
 fn main() {
     fern_setup();
-    let cs = ComponentStore {
-        store: vec![
-            Rc::new(ProbeEdit::new("probe_edit", (100.0, 100.0))),
-            Rc::new(Probe {
-                id: "probe".to_string(),
-                pos: (250.0, 100.0),
-                input: Input::new("probe_edit", "out"),
-            }),
-        ],
-    };
+    let cs = model();
 
     let path = PathBuf::from("probe_edit.json");
-    cs.save_file(&path);
+    cs.save_file(&path).unwrap();
 
     #[cfg(feature = "gui-egui")]
     syncrim::gui_egui::gui(&cs, &path).ok();
@@ -27,3 +18,17 @@ fn main() {
     #[cfg(feature = "gui-vizia")]
     syncrim::gui_vizia::gui(&cs, &path);
 }
+
+// the model of the example, also built by `tests/examples.rs`
+pub fn model() -> ComponentStore {
+    ComponentStore {
+        store: vec![
+            Rc::new(ProbeEdit::new("probe_edit", (100.0, 100.0))),
+            Rc::new(Probe {
+                id: "probe".to_string(),
+                pos: (250.0, 100.0),
+                input: Input::new("probe_edit", "out"),
+            }),
+        ],
+    }
+}
@@ -10,13 +10,28 @@ use syncrim::{
 
 fn main() {
     fern_setup();
-    let cs = ComponentStore {
+    let cs = model();
+
+    let path = PathBuf::from("mips.json");
+    cs.save_file(&path).unwrap();
+
+    #[cfg(feature = "gui-egui")]
+    syncrim::gui_egui::gui(&cs, &path).ok();
+
+    #[cfg(feature = "gui-vizia")]
+    syncrim::gui_vizia::gui(&cs, &path);
+}
+
+// the model of the example, also built by `tests/examples.rs`
+pub fn model() -> ComponentStore {
+    ComponentStore {
         store: vec![
             Rc::new(Add {
                 id: "add".to_string(),
                 pos: (200.0, 120.0),
                 a_in: Input::new("c1", "out"),
                 b_in: Input::new("reg", "out"),
+                width: 32,
             }),
             Rc::new(Constant {
                 id: "c1".to_string(),
@@ -106,14 +121,5 @@ fn main() {
                 input: Input::new("instr_mem", "out"),
             }),
         ],
-    };
-
-    let path = PathBuf::from("mips.json");
-    cs.save_file(&path);
-
-    #[cfg(feature = "gui-egui")]
-    syncrim::gui_egui::gui(&cs, &path).ok();
-
-    #[cfg(feature = "gui-vizia")]
-    syncrim::gui_vizia::gui(&cs, &path);
+    }
 }
@@ -9,7 +9,21 @@ use syncrim::{
 // TODO: fix wires and layout
 fn main() {
     fern_setup();
-    let cs = ComponentStore {
+    let cs = model();
+
+    let path = PathBuf::from("reg_file.json");
+    cs.save_file(&path).unwrap();
+
+    #[cfg(feature = "gui-egui")]
+    syncrim::gui_egui::gui(&cs, &path).ok();
+
+    #[cfg(feature = "gui-vizia")]
+    syncrim::gui_vizia::gui(&cs, &path);
+}
+
+// the model of the example, also built by `tests/examples.rs`
+pub fn model() -> ComponentStore {
+    ComponentStore {
         store: vec![
             Rc::new(Constant {
                 id: "c_read_reg_1".to_string(),
@@ -52,17 +66,9 @@ fn main() {
 
                 // data
                 registers: RegStore::new(),
+                unknown: Default::default(),
                 history: RegHistory::new(),
             }),
         ],
-    };
-
-    let path = PathBuf::from("reg_file.json");
-    cs.save_file(&path);
-
-    #[cfg(feature = "gui-egui")]
-    syncrim::gui_egui::gui(&cs, &path).ok();
-
-    #[cfg(feature = "gui-vizia")]
-    syncrim::gui_vizia::gui(&cs, &path);
+    }
 }
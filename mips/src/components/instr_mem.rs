@@ -1,41 +1,49 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use syncrim::common::{Component, Input, OutputType, Ports, Simulator};
+use syncrim::{
+    breakpoint::Access,
+    common::{component, Component, ComponentPorts, ComponentSchema, Input, Signal, Simulator},
+};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(out(width = 32))]
 pub struct InstrMem {
     pub id: String,
     pub pos: (f32, f32),
     pub instr: Vec<u32>,
+    #[input]
     pub pc: Input,
 }
 
 use log::*;
 
+#[component]
 #[typetag::serde()]
 impl Component for InstrMem {
     fn to_(&self) {
         trace!("InstrMem");
     }
 
-    fn get_id_ports(&self) -> (String, Ports) {
-        (
-            self.id.clone(),
-            Ports {
-                inputs: vec![self.pc.clone()],
-                out_type: OutputType::Combinatorial,
-                outputs: vec!["out".into()],
-            },
-        )
-    }
-
     fn clock(&self, simulator: &mut Simulator) {
         // get instr at pc/4
-        let pc = simulator.get_input_val(&self.pc);
+        let pc = simulator.get_input(0);
+        // an unknown pc fetches an unknown (X) instruction
+        if simulator.get_input_xz(0) != 0 {
+            InstrMem::set_out_xz(simulator, Signal::MAX, Signal::MAX);
+            return;
+        }
 
         trace!("--- evaluate instr mem: pc {}", pc);
+        // instruction fetch, hitting PC breakpoints
+        simulator.watch_address(&self.id, pc as usize, 4, Access::Read);
         let instr = self.instr[(pc / 4) as usize];
         // set output
         trace!("--- output {}", instr);
-        simulator.set_out_val(&self.id, "out", instr);
+        InstrMem::set_out(simulator, instr as Signal);
+    }
+
+    // each fetch is reported
+    fn always_evaluate(&self) -> bool {
+        true
     }
 }
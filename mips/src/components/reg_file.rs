@@ -1,9 +1,16 @@
 use log::*;
 use num_enum::TryFromPrimitive;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::ops::{Deref, Range};
-use std::{cell::RefCell, rc::Rc};
-use syncrim::common::{Component, Input, OutputType, Ports, Signal, Simulator};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+use syncrim::common::{
+    component, Component, ComponentPorts, ComponentSchema, Input, Signal, Simulator,
+};
 
 #[allow(non_camel_case_types)]
 #[rustfmt::skip]
@@ -44,7 +51,8 @@ pub enum Reg {
     ra      = 31,   // Return address (used by function calls)
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(reg_a(width = 32), reg_b(width = 32))]
 pub struct RegFile {
     pub id: String,
     pub pos: (f32, f32),
@@ -52,31 +60,107 @@ pub struct RegFile {
     pub height: f32,
 
     // ports
+    #[input(width = 5)]
     pub read_addr1: Input,
+    #[input(width = 5)]
     pub read_addr2: Input,
+    #[input(width = 32)]
     pub write_data: Input,
+    #[input(width = 5)]
     pub write_addr: Input,
+    #[input(width = 1)]
     pub write_enable: Input,
 
     // data
     pub registers: RegStore,
+    // unknown (X) bits of the registers in four-state mode, saved with the
+    // registers, models without them have the registers holding 0 unknown
+    // (not yet written), see `RegFile::unknown`
+    #[serde(default, skip_serializing_if = "is_none")]
+    pub unknown: RefCell<Option<RegStore>>,
+    #[serde(skip)]
     pub history: RegHistory,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+// a register write, with the previous register value and unknown bits
+#[derive(Clone, Debug)]
 pub struct RegOp {
-    read_addr1: u8,
-    read_addr2: u8,
-    write_addr2: Option<(u8, u32)>,
-    old_data: Option<u8>,
+    clock: usize,
+    write_addr: u8,
+    old_data: u32,
+    old_xz: u32,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct RegHistory(RefCell<Vec<RegOp>>);
+// undo log of register writes, for reverse simulation
+#[derive(Clone, Debug)]
+pub struct RegHistory {
+    clock: Cell<usize>,
+    ops: RefCell<Vec<RegOp>>,
+    // the initial values (and unknown bits) of the registers written by
+    // forgotten ops
+    initial: RefCell<HashMap<u8, (u32, u32)>>,
+}
 
 impl RegHistory {
     pub fn new() -> Self {
-        RegHistory(RefCell::new(Vec::new()))
+        RegHistory {
+            clock: Cell::new(0),
+            ops: RefCell::new(Vec::new()),
+            initial: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // advance the clock of the undo log
+    fn clock(&self) {
+        self.clock.set(self.clock.get() + 1);
+    }
+
+    fn push(&self, write_addr: u8, old_data: u32, old_xz: u32) {
+        self.ops.borrow_mut().push(RegOp {
+            clock: self.clock.get(),
+            write_addr,
+            old_data,
+            old_xz,
+        });
+    }
+
+    // revert the writes of the current clock
+    fn un_clock(&self, registers: &RegStore, unknown: &RegStore) {
+        let clock = self.clock.get();
+        let mut ops = self.ops.borrow_mut();
+        while matches!(ops.last(), Some(op) if op.clock == clock) {
+            let op = ops.pop().unwrap();
+            registers.borrow_mut()[op.write_addr as usize] = op.old_data;
+            unknown.borrow_mut()[op.write_addr as usize] = op.old_xz;
+        }
+        self.clock.set(clock.saturating_sub(1));
+    }
+
+    // revert all writes, restoring the initial registers
+    fn reset(&self, registers: &RegStore, unknown: &RegStore) {
+        for op in self.ops.borrow_mut().drain(..).rev() {
+            registers.borrow_mut()[op.write_addr as usize] = op.old_data;
+            unknown.borrow_mut()[op.write_addr as usize] = op.old_xz;
+        }
+        for (write_addr, (old_data, old_xz)) in self.initial.borrow_mut().drain() {
+            registers.borrow_mut()[write_addr as usize] = old_data;
+            unknown.borrow_mut()[write_addr as usize] = old_xz;
+        }
+        self.clock.set(0);
+    }
+
+    // forget the writes of all but the last `depth` clocks, keeping the
+    // initial values for reset
+    fn forget_older(&self, depth: usize) {
+        let oldest = self.clock.get().saturating_sub(depth);
+        let mut ops = self.ops.borrow_mut();
+        let forgotten = ops.partition_point(|op| op.clock <= oldest);
+        let mut initial = self.initial.borrow_mut();
+        for op in ops.drain(..forgotten) {
+            initial
+                .entry(op.write_addr)
+                .or_insert((op.old_data, op.old_xz));
+        }
     }
 }
 
@@ -86,7 +170,7 @@ impl Default for RegHistory {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct RegStore(pub Rc<RefCell<[u32; 32]>>);
 
 impl RegStore {
@@ -121,53 +205,101 @@ impl Deref for RegStore {
     }
 }
 
+fn is_none(unknown: &RefCell<Option<RegStore>>) -> bool {
+    unknown.borrow().is_none()
+}
+
 impl RegFile {
-    fn read_reg(&self, simulator: &Simulator, input: &Input) -> u32 {
+    // the unknown bits of the registers, if not given by the model all
+    // bits of the registers holding 0 (i.e., not yet written)
+    fn unknown(&self) -> RegStore {
+        self.unknown
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                let registers = self.registers.borrow();
+                RegStore(Rc::new(RefCell::new(registers.map(|r| {
+                    if r == 0 {
+                        u32::MAX
+                    } else {
+                        0
+                    }
+                }))))
+            })
+            .clone()
+    }
+
+    // the (value, unknown bits) of the register addressed by input `port`,
+    // unknown (X) if the address is unknown or the register not yet written
+    fn read_reg(&self, simulator: &Simulator, port: usize, input: &Input) -> (Signal, Signal) {
+        if simulator.get_input_xz(port) != 0 {
+            return (Signal::MAX, Signal::MAX);
+        }
         let read_addr = simulator.get_input_val(input) as usize;
         trace!("read_addr {}", read_addr);
 
         // mips always reads 0;
         if read_addr > 0 {
-            self.registers.borrow()[read_addr]
+            // unknown bits (four-state mode only) read as X (value bit 1),
+            // also if written as Z
+            let xz = match simulator.four_state {
+                true => self.unknown().borrow()[read_addr] as Signal,
+                false => 0,
+            };
+            (self.registers.borrow()[read_addr] as Signal | xz, xz)
         } else {
-            0
+            (0, 0)
         }
     }
 }
 
+#[component]
 #[typetag::serde()]
 impl Component for RegFile {
     fn to_(&self) {
         trace!("RegFile");
     }
 
-    fn get_id_ports(&self) -> (String, Ports) {
-        (
-            self.id.clone(),
-            Ports {
-                inputs: vec![self.read_addr1.clone(), self.read_addr2.clone()],
-                out_type: OutputType::Combinatorial,
-                outputs: vec!["reg_a".into(), "reg_b".into()],
-            },
-        )
-    }
-
     fn clock(&self, simulator: &mut Simulator) {
-        if simulator.get_input_val(&self.write_enable) == true as Signal {
-            let data = simulator.get_input_val(&self.write_data);
-            trace!("data {}", data);
+        self.history.clock();
+        let unknown = self.unknown();
+        // no write with an unknown enable or address, the unknown bits of
+        // the data are written along with the value
+        let known = simulator.get_input_xz(3) == 0 && simulator.get_input_xz(4) == 0;
+        if known && simulator.get_input_val(&self.write_enable) == true as Signal {
+            let data = simulator.get_input_val(&self.write_data) as u32;
+            let xz = simulator.get_input_xz(2) as u32;
+            trace!("data {} xz {:#x}", data, xz);
             let write_addr = simulator.get_input_val(&self.write_addr) as usize;
             trace!("write_addr {}", write_addr);
-            self.registers.borrow_mut()[write_addr] = data;
+            let old_data = std::mem::replace(&mut self.registers.borrow_mut()[write_addr], data);
+            let old_xz = std::mem::replace(&mut unknown.borrow_mut()[write_addr], xz);
+            self.history.push(write_addr as u8, old_data, old_xz);
         }
 
         // read after write
-        let reg_value_a = self.read_reg(simulator, &self.read_addr1);
+        let (reg_value_a, xz) = self.read_reg(simulator, 0, &self.read_addr1);
         trace!("reg_value {}", reg_value_a);
-        simulator.set_out_val(&self.id, "reg_a", reg_value_a);
+        RegFile::set_reg_a_xz(simulator, reg_value_a, xz);
 
-        let reg_value_b = self.read_reg(simulator, &self.read_addr2);
+        let (reg_value_b, xz) = self.read_reg(simulator, 1, &self.read_addr2);
         trace!("reg_value {}", reg_value_b);
-        simulator.set_out_val(&self.id, "reg_b", reg_value_b);
+        RegFile::set_reg_b_xz(simulator, reg_value_b, xz);
+    }
+
+    fn un_clock(&self) {
+        self.history.un_clock(&self.registers, &self.unknown());
+    }
+
+    fn reset(&self) {
+        self.history.reset(&self.registers, &self.unknown());
+    }
+
+    fn forget_older(&self, depth: usize) {
+        self.history.forget_older(depth);
+    }
+
+    // the write history is clocked, also when the inputs are unchanged
+    fn always_evaluate(&self) -> bool {
+        true
     }
 }
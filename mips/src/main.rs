@@ -1,12 +1,29 @@
+use clap::Parser;
+use log::*;
 #[allow(unused_imports)]
 use mips::components::*;
 use std::path::PathBuf;
-use syncrim::{common::ComponentStore, fern::fern_setup};
+use syncrim::{
+    cli::{self, Args},
+    common::ComponentStore,
+    fern::fern_setup,
+};
 fn main() {
+    let args = Args::parse();
+    // sub-commands (e.g., `schema`) include the MIPS components
+    if let Some(command) = args.command {
+        std::process::exit(cli::execute(command));
+    }
     fern_setup();
 
-    let path = PathBuf::from("mips.json");
-    let _cs = ComponentStore::load_file(&path);
+    let path = PathBuf::from(args.model.unwrap_or("mips.json".into()));
+    let _cs = match ComponentStore::load_file(&path) {
+        Ok(cs) => cs,
+        Err(err) => {
+            error!("Failed to load model: {}", err);
+            std::process::exit(1);
+        }
+    };
 
     #[cfg(feature = "gui-vizia")]
     syncrim::gui_vizia::gui(&_cs, &path);
@@ -1,14 +1,15 @@
 use mips::components::{RegFile, RegHistory, RegStore};
 use std::rc::Rc;
 use syncrim::{
+    checkpoint::Checkpoint,
     common::{ComponentStore, Input, Signal, Simulator},
     components::*,
+    schema,
 };
 
-// an example of integration test for a mips specific component
-#[test]
-fn test_reg_file() {
-    let cs = ComponentStore {
+// a register file with its inputs driven by probes
+fn reg_file() -> ComponentStore {
+    ComponentStore {
         store: vec![
             Rc::new(ProbeOut::new("read_reg_1")),
             Rc::new(ProbeOut::new("read_reg_2")),
@@ -31,12 +32,19 @@ fn test_reg_file() {
 
                 // data
                 registers: RegStore::new(),
+                unknown: Default::default(),
                 history: RegHistory::new(),
             }),
         ],
-    };
+    }
+}
+
+// an example of integration test for a mips specific component
+#[test]
+fn test_reg_file() {
+    let cs = reg_file();
     let mut clock = 0;
-    let mut simulator = Simulator::new(&cs, &mut clock);
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
 
     assert_eq!(clock, 1);
 
@@ -77,12 +85,145 @@ fn test_reg_file() {
     assert_eq!(clock, 3);
     assert_eq!(simulator.get_input_val(out_reg_1), 0);
     assert_eq!(simulator.get_input_val(out_reg_2), 1337);
+
+    // step back before the write to reg # 1, and read it again
+    println!("<un_clock>");
+    simulator.un_clock(&mut clock);
+    simulator.un_clock(&mut clock);
+    assert_eq!(clock, 1);
+    simulator.set_out_val("read_reg_2", "out", 1);
+    simulator.set_out_val("write_enable", "out", false as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(clock, 2);
+    assert_eq!(simulator.get_input_val(out_reg_2), 0);
+
+    // reset restores the initial registers
+    println!("<setup for clock 3>");
+    simulator.set_out_val("write_data", "out", 7);
+    simulator.set_out_val("write_enable", "out", true as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.get_input_val(out_reg_2), 7);
+    simulator.reset(&mut clock);
+    simulator.set_out_val("read_reg_2", "out", 1);
+    simulator.set_out_val("write_enable", "out", false as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.get_input_val(out_reg_2), 0);
+}
+
+// in four-state mode the registers are unknown (X) until written, and keep
+// the unknown bits of the written data
+#[test]
+fn test_reg_file_four_state() {
+    let cs = reg_file();
+    let mut clock = 0;
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+    simulator.set_four_state(true, &mut clock);
+    let out_reg_1 = &Input::new("reg_file", "reg_a");
+    let out_reg_2 = &Input::new("reg_file", "reg_b");
+
+    // register 1 not yet written, register 0 always reads 0
+    simulator.set_out_val("read_reg_1", "out", 0);
+    simulator.set_out_val("read_reg_2", "out", 1);
+    simulator.set_out_val("write_addr", "out", 1);
+    simulator.set_out_val("write_enable", "out", false as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.format_input(out_reg_1), "0");
+    assert_eq!(simulator.format_input(out_reg_2), "X");
+
+    // writing unknown data (the write_data probe is not set)
+    simulator.set_out_val("write_enable", "out", true as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.format_input(out_reg_2), "X");
+
+    // writing known data, and stepping back to the unknown data
+    simulator.set_out_val("write_data", "out", 1337);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.format_input(out_reg_2), "1337");
+    simulator.un_clock(&mut clock);
+    simulator.set_out_val("write_enable", "out", false as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.format_input(out_reg_2), "X");
+
+    // reset makes the registers unknown again
+    simulator.set_out_val("write_enable", "out", true as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.format_input(out_reg_2), "1337");
+    simulator.reset(&mut clock);
+    simulator.set_out_val("write_enable", "out", false as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.format_input(out_reg_2), "X");
+}
+
+// the unknown bits of the registers are saved with the registers, e.g., in
+// checkpoints
+#[test]
+fn test_reg_file_checkpoint() {
+    let cs = reg_file();
+    let mut clock = 0;
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+    simulator.set_four_state(true, &mut clock);
+    let out_reg_1 = &Input::new("reg_file", "reg_a");
+    let out_reg_2 = &Input::new("reg_file", "reg_b");
+
+    // write register 1, register 2 is not written
+    simulator.set_out_val("read_reg_1", "out", 1);
+    simulator.set_out_val("read_reg_2", "out", 2);
+    simulator.set_out_val("write_data", "out", 1337);
+    simulator.set_out_val("write_addr", "out", 1);
+    simulator.set_out_val("write_enable", "out", true as Signal);
+    simulator.clock(&mut clock);
+    simulator.set_out_val("write_enable", "out", false as Signal);
+    simulator.clock(&mut clock);
+
+    let json = serde_json::to_string(&simulator.checkpoint()).unwrap();
+    let mut restored_clock = 0;
+    let mut restored =
+        Simulator::restore(&Checkpoint::load(&json).unwrap(), &mut restored_clock).unwrap();
+    restored.clock(&mut restored_clock);
+    assert_eq!(restored.format_input(out_reg_1), "1337");
+    assert_eq!(restored.format_input(out_reg_2), "X");
+}
+
+// registers given a value by a model without unknown bits are known
+#[test]
+fn test_reg_file_model_registers() {
+    let mut model = serde_json::to_value(reg_file()).unwrap();
+    assert!(model["store"][5].get("unknown").is_none());
+    model["store"][5]["registers"][1] = 7.into();
+    let cs = ComponentStore::load(&model.to_string()).unwrap();
+    let mut clock = 0;
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+    simulator.set_four_state(true, &mut clock);
+
+    simulator.set_out_val("read_reg_1", "out", 1);
+    simulator.set_out_val("read_reg_2", "out", 2);
+    simulator.set_out_val("write_enable", "out", false as Signal);
+    simulator.clock(&mut clock);
+    assert_eq!(
+        simulator.format_input(&Input::new("reg_file", "reg_a")),
+        "7"
+    );
+    assert_eq!(
+        simulator.format_input(&Input::new("reg_file", "reg_b")),
+        "X"
+    );
 }
 
 // An example of a test that should panic (fail)
 // Useful to assert that illegal models and/or states does not pass unnoticed
 #[test]
 #[should_panic(expected = "assertion failed")]
+#[allow(clippy::assertions_on_constants)]
 fn should_fail() {
     assert!(false)
 }
+
+#[test]
+fn test_schema() {
+    // the MIPS components are registered along with the core components
+    let names: Vec<&str> = schema::component_types().iter().map(|t| t.name).collect();
+    for name in ["Add", "InstrMem", "RegFile"] {
+        assert!(names.contains(&name), "{} not in {:?}", name, names);
+    }
+    assert!(schema::unregistered_types().is_empty());
+}
@@ -0,0 +1,36 @@
+// The models of the examples build without lint errors and simulate, each
+// example included as a module (its `main` is not used)
+use syncrim::{
+    common::{ComponentStore, Simulator},
+    lint::{self, Severity},
+};
+
+#[allow(dead_code)]
+#[path = "../examples/mips.rs"]
+mod mips_example;
+#[allow(dead_code)]
+#[path = "../examples/reg_file.rs"]
+mod reg_file;
+
+#[test]
+fn test_examples() {
+    let models: Vec<(&str, ComponentStore)> = vec![
+        ("mips", mips_example::model()),
+        ("reg_file", reg_file::model()),
+    ];
+    for (name, cs) in models {
+        let errors: Vec<String> = lint::check(&cs)
+            .iter()
+            .filter(|p| p.severity() == Severity::Error)
+            .map(|p| p.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{}: {:?}", name, errors);
+
+        let mut clock = 0;
+        let mut simulator =
+            Simulator::new(&cs, &mut clock).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        for _ in 0..8 {
+            simulator.clock(&mut clock);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+// Breakpoints on signals, and watchpoints on memory addresses
+//
+// Signal breakpoints are checked after each clock. Conditions on the value
+// (`=`, `>`, mask) fire when the condition becomes true, i.e., it holds for
+// the new state but not for the previous state. Address watchpoints are
+// reported by memory components (e.g., `Mem`) on each access, through
+// `Simulator::watch_address`.
+use crate::common::{parse_signal, Id, Input, Signal, SimError, Simulator};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Equals(Signal),
+    Changes,
+    Greater(Signal),
+    // (signal & mask) == value
+    Mask { mask: Signal, value: Signal },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Any,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    Signal { input: Input, condition: Condition },
+    // an access of memory component `id` covering `addr`
+    Address { id: Id, addr: usize, access: Access },
+}
+
+// A breakpoint hit at `clock`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hit {
+    pub clock: usize,
+    pub breakpoint: Breakpoint,
+}
+
+impl Condition {
+    fn holds(&self, value: Signal) -> bool {
+        match self {
+            Condition::Equals(v) => value == *v,
+            Condition::Greater(v) => value > *v,
+            Condition::Mask { mask, value: v } => value & mask == *v,
+            Condition::Changes => false,
+        }
+    }
+
+    fn hit(&self, prev: Signal, value: Signal) -> bool {
+        match self {
+            Condition::Changes => prev != value,
+            _ => self.holds(value) && !self.holds(prev),
+        }
+    }
+}
+
+// Breakpoints use the syntax of the command line:
+//
+// - `id.field=value`, `id.field>value`, `id.field&mask=value`
+// - `id.field`, the signal changes
+// - `id@addr`, `id@addr:r`, `id@addr:w`, memory access (any, read, write)
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Signal { input, condition } => match condition {
+                Condition::Equals(v) => write!(f, "{}={}", input, v),
+                Condition::Changes => write!(f, "{}", input),
+                Condition::Greater(v) => write!(f, "{}>{}", input, v),
+                Condition::Mask { mask, value } => {
+                    write!(f, "{}&0x{:x}=0x{:x}", input, mask, value)
+                }
+            },
+            Breakpoint::Address { id, addr, access } => {
+                write!(f, "{}@0x{:x}", id, addr)?;
+                match access {
+                    Access::Read => write!(f, ":r"),
+                    Access::Write => write!(f, ":w"),
+                    Access::Any => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((id, addr)) = s.split_once('@') {
+            let (addr, access) = match addr.rsplit_once(':') {
+                Some((addr, "r")) => (addr, Access::Read),
+                Some((addr, "w")) => (addr, Access::Write),
+                Some(_) => return Err(anyhow!("Unknown access in {:?}, use `:r` or `:w`", s)),
+                None => (addr, Access::Any),
+            };
+            return Ok(Breakpoint::Address {
+                id: id.into(),
+                addr: parse_signal(addr)? as usize,
+                access,
+            });
+        }
+        let (signal, condition) = match s.find(['=', '>', '&']) {
+            None => (s, Condition::Changes),
+            Some(i) => {
+                let (signal, rest) = s.split_at(i);
+                let condition = match rest.split_at(1) {
+                    ("=", value) => Condition::Equals(parse_signal(value)?),
+                    (">", value) => Condition::Greater(parse_signal(value)?),
+                    (_, mask) => {
+                        let (mask, value) = mask.split_once('=').ok_or_else(|| {
+                            anyhow!("Failed to parse {:?}, expected `id.field&mask=value`", s)
+                        })?;
+                        Condition::Mask {
+                            mask: parse_signal(mask)?,
+                            value: parse_signal(value)?,
+                        }
+                    }
+                };
+                (signal, condition)
+            }
+        };
+        Ok(Breakpoint::Signal {
+            input: signal.parse()?,
+            condition,
+        })
+    }
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Breakpoint {} hit at clock {}",
+            self.breakpoint, self.clock
+        )
+    }
+}
+
+impl Simulator {
+    /// add a breakpoint, checking that the signal or component exists
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<(), SimError> {
+        match &breakpoint {
+            Breakpoint::Signal { input, .. } => {
+                self.get_input_index(input)?;
+            }
+            Breakpoint::Address { id, .. } => {
+                if !self.id_start_index.contains_key(id) {
+                    return Err(SimError::UnknownId(id.clone()));
+                }
+            }
+        }
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    /// remove breakpoint `index`
+    pub fn remove_breakpoint(&mut self, index: usize) -> Breakpoint {
+        self.breakpoints.remove(index)
+    }
+
+    /// report an access of `size` bytes at `addr` by memory component `id`,
+    /// hitting any watchpoint covering the accessed bytes
+    pub fn watch_address(&mut self, id: &str, addr: usize, size: usize, access: Access) {
+        let clock = self.history.len();
+        let hits = self.breakpoints.iter().filter(|b| match b {
+            Breakpoint::Address {
+                id: b_id,
+                addr: b_addr,
+                access: b_access,
+            } => {
+                b_id == id
+                    && (addr..addr + size).contains(b_addr)
+                    && (*b_access == Access::Any || *b_access == access)
+            }
+            _ => false,
+        });
+        let hits: Vec<Hit> = hits
+            .map(|b| Hit {
+                clock,
+                breakpoint: b.clone(),
+            })
+            .collect();
+        self.hits.extend(hits);
+    }
+
+    // check the signal breakpoints, `prev` is the state before the clock
+    pub(crate) fn check_breakpoints(&mut self, prev: &[Signal]) {
+        let clock = self.history.len();
+        for breakpoint in &self.breakpoints {
+            if let Breakpoint::Signal { input, condition } = breakpoint {
+                // breakpoints are checked when added
+                let index = self.get_input_index(input).unwrap();
+                if condition.hit(prev[index], self.sim_state[index]) {
+                    self.hits.push(Hit {
+                        clock,
+                        breakpoint: breakpoint.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// clock until a breakpoint is hit, or `clock` reaches `max_clock`,
+    /// returns the breakpoints hit (if any)
+    pub fn run(&mut self, clock: &mut usize, max_clock: usize) -> &[Hit] {
+        while *clock < max_clock {
+            self.clock(clock);
+            if !self.hits.is_empty() {
+                break;
+            }
+        }
+        &self.hits
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::counter;
+
+    #[test]
+    fn test_parse() {
+        for text in [
+            "reg.out=12",
+            "reg.out",
+            "reg.out>5",
+            "reg.out&0xf=0x3",
+            "mem@0x10",
+            "mem@0x10:w",
+        ] {
+            let breakpoint: Breakpoint = text.parse().unwrap();
+            assert_eq!(breakpoint.to_string(), text);
+        }
+        assert_eq!(
+            "reg.out&3=1".parse::<Breakpoint>().unwrap(),
+            Breakpoint::Signal {
+                input: Input::new("reg", "out"),
+                condition: Condition::Mask { mask: 3, value: 1 }
+            }
+        );
+        assert!("reg.out&3".parse::<Breakpoint>().is_err());
+        assert!("mem@0x10:x".parse::<Breakpoint>().is_err());
+    }
+
+    #[test]
+    fn test_break() {
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(3), &mut clock).unwrap();
+        // reg.out = 3 * (clock - 1)
+        simulator
+            .add_breakpoint("reg.out>10".parse().unwrap())
+            .unwrap();
+        simulator
+            .add_breakpoint("reg.out&0x7=0x2".parse().unwrap())
+            .unwrap();
+        assert!(simulator
+            .add_breakpoint("reg.missing".parse().unwrap())
+            .is_err());
+        assert!(simulator
+            .add_breakpoint("missing@0x0".parse().unwrap())
+            .is_err());
+
+        // 3, 6, 9, 12 (> 10)
+        let hits = simulator.run(&mut clock, 100).to_vec();
+        assert_eq!(clock, 5);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].to_string(), "Breakpoint reg.out>10 hit at clock 5");
+
+        // 15, 18 (18 & 7 = 2), > 10 still holds and does not fire again
+        let hits = simulator.run(&mut clock, 100).to_vec();
+        assert_eq!(clock, 7);
+        assert_eq!(hits[0].breakpoint, simulator.breakpoints[1]);
+
+        simulator.remove_breakpoint(1);
+        simulator
+            .add_breakpoint("add.out".parse().unwrap())
+            .unwrap();
+        assert_eq!(simulator.run(&mut clock, 100).len(), 1);
+        assert_eq!(clock, 8);
+
+        // no hit before reaching the limit
+        simulator.remove_breakpoint(1);
+        assert!(simulator.run(&mut clock, 20).is_empty());
+        assert_eq!(clock, 20);
+    }
+}
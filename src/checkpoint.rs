@@ -0,0 +1,240 @@
+// Checkpoints, the complete state of a simulation saved as a single file.
+//
+// Component internal state (e.g., `Memory` bytes, `RegStore`, `ProbeEdit`
+// history) is part of the serialized components, so the model is stored
+// as is. Of the history only the policy is stored, a restored simulation
+// cannot step back beyond the checkpoint. The breakpoints and the evaluation
+// mode are restored, the breakpoints hit by the last clock are not.
+use crate::breakpoint::Breakpoint;
+use crate::common::{ComponentStore, EvalMode, Signal, SimError, Simulator};
+use crate::history::History;
+use crate::migrate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs, path::PathBuf};
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub model: ComponentStore,
+    pub clock: usize,
+    pub sim_state: Vec<Signal>,
+    pub history: HistoryPolicy,
+    // absent in checkpoints saved without them
+    #[serde(default)]
+    pub breakpoints: Vec<Breakpoint>,
+    #[serde(default)]
+    pub eval_mode: EvalMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryPolicy {
+    pub snapshot_interval: usize,
+    pub max_depth: Option<usize>,
+}
+
+impl Checkpoint {
+    /// load a checkpoint, upgrading the model to the current version
+    pub fn load(json: &str) -> Result<Self, SimError> {
+        let parse_err = |err: serde_path_to_error::Error<serde_json::Error>| SimError::Parse {
+            path: err.path().to_string(),
+            msg: err.inner().to_string(),
+        };
+        let de = &mut serde_json::Deserializer::from_str(json);
+        let mut checkpoint: Value = serde_path_to_error::deserialize(de).map_err(parse_err)?;
+        if let Some(model) = checkpoint.get_mut("model") {
+            migrate::upgrade(model)?;
+        }
+        serde_path_to_error::deserialize(checkpoint).map_err(parse_err)
+    }
+
+    pub fn load_file(path: &PathBuf) -> Result<Self, SimError> {
+        let json = fs::read_to_string(path).map_err(|error| SimError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        Checkpoint::load(&json)
+    }
+
+    pub fn save_file(&self, path: &PathBuf) -> Result<(), SimError> {
+        let json = serde_json::to_string(self).map_err(|e| SimError::Serialize(e.to_string()))?;
+        fs::write(path, json).map_err(|error| SimError::Io {
+            path: path.to_owned(),
+            error,
+        })
+    }
+}
+
+impl Simulator {
+    /// the complete simulation state at the current clock
+    pub fn checkpoint(&self) -> Checkpoint {
+        // components in model order, as the order determines output indexes
+        let mut store = self.ordered_components.clone();
+        store.sort_by_key(|c| self.get_id_start_index(&c.get_id_ports().0));
+        Checkpoint {
+            model: ComponentStore { store },
+            clock: self.history.len(),
+            sim_state: self.sim_state.clone(),
+            history: HistoryPolicy {
+                snapshot_interval: self.history.interval(),
+                max_depth: self.history.max_depth(),
+            },
+            breakpoints: self.breakpoints.clone(),
+            eval_mode: self.eval_mode,
+        }
+    }
+
+    /// restore a simulation from `checkpoint`, setting `clock`
+    pub fn restore(checkpoint: &Checkpoint, clock: &mut usize) -> Result<Self, SimError> {
+        let mut simulator = Simulator::build(&checkpoint.model)?;
+        // in four-state mode the values are followed by the unknown bits
+        let n = simulator.sim_state.len();
+        if checkpoint.sim_state.len() != n && checkpoint.sim_state.len() != 2 * n {
+            return Err(SimError::Parse {
+                path: "sim_state".into(),
+                msg: format!(
+                    "expected {} or {} values, found {}",
+                    n,
+                    2 * n,
+                    checkpoint.sim_state.len()
+                ),
+            });
+        }
+        simulator.four_state = checkpoint.sim_state.len() == 2 * n;
+        simulator.init_state();
+        simulator.sim_state = checkpoint.sim_state.clone();
+        simulator.history = History::new(
+            checkpoint.history.snapshot_interval,
+            checkpoint.history.max_depth,
+        );
+        simulator.history.restart(checkpoint.clock);
+        for breakpoint in &checkpoint.breakpoints {
+            simulator.add_breakpoint(breakpoint.clone())?;
+        }
+        simulator.eval_mode = checkpoint.eval_mode;
+        *clock = checkpoint.clock;
+        Ok(simulator)
+    }
+
+    /// save a checkpoint of the current simulation state
+    pub fn save_checkpoint(&self, path: &PathBuf) -> Result<(), SimError> {
+        self.checkpoint().save_file(path)
+    }
+
+    /// restore a simulation from a checkpoint file, setting `clock`
+    pub fn load_checkpoint(path: &PathBuf, clock: &mut usize) -> Result<Self, SimError> {
+        Simulator::restore(&Checkpoint::load_file(path)?, clock)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::Input;
+    use crate::components::*;
+    use crate::fixtures::counter;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_checkpoint() {
+        // counter, with an editable increment
+        let inc = Rc::new(ProbeEdit::new("c", (0.0, 0.0)));
+        let mut cs = counter(0);
+        cs.store[0] = inc.clone();
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_history_depth(Some(10));
+
+        // edit the increment to 3, as the GUI would
+        inc.history.write().unwrap().last_mut().unwrap().signal = 3;
+        for _ in 0..4 {
+            simulator.clock(&mut clock);
+        }
+        assert_eq!(clock, 5);
+
+        let json = serde_json::to_string(&simulator.checkpoint()).unwrap();
+        let mut restored_clock = 0;
+        let mut restored =
+            Simulator::restore(&Checkpoint::load(&json).unwrap(), &mut restored_clock).unwrap();
+        assert_eq!(restored_clock, 5);
+        assert_eq!(restored.sim_state, simulator.sim_state);
+        assert_eq!(restored.history.max_depth(), Some(10));
+
+        for _ in 0..3 {
+            simulator.clock(&mut clock);
+            restored.clock(&mut restored_clock);
+        }
+        assert_eq!(restored_clock, 8);
+        assert_eq!(restored.sim_state, simulator.sim_state);
+        assert_eq!(restored.get_input_val(&Input::new("reg", "out")), 18);
+
+        // stepping back is limited to the checkpoint
+        for _ in 0..5 {
+            restored.un_clock(&mut restored_clock);
+        }
+        assert_eq!(restored_clock, 5);
+    }
+
+    #[test]
+    fn test_checkpoint_four_state() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("data")),
+                Rc::new(ProbeOut::new("addr")),
+                Rc::new(ProbeOut::new("ctrl")),
+                Rc::new(ProbeOut::new("size")),
+                Rc::new(ProbeOut::new("sign")),
+                Rc::new(Mem {
+                    id: "mem".into(),
+                    pos: (0.0, 0.0),
+                    width: 0.0,
+                    height: 0.0,
+                    big_endian: false,
+                    data: Input::new("data", "out"),
+                    addr: Input::new("addr", "out"),
+                    ctrl: Input::new("ctrl", "out"),
+                    size: Input::new("size", "out"),
+                    sign: Input::new("sign", "out"),
+                    memory: Memory::new(),
+                }),
+            ],
+        };
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_four_state(true, &mut clock);
+        let data = Input::new("mem", "data");
+
+        // write 0x42 to addr 4, then read the undefined (X) byte at addr 5
+        simulator.set_out_val("data", "out", 0x42);
+        simulator.set_out_val("addr", "out", 4);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.set_out_val("size", "out", 1);
+        simulator.set_out_val("sign", "out", 0);
+        simulator.clock(&mut clock);
+        simulator.set_out_val("addr", "out", 5);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(&data), "0x00000000000000XX");
+
+        simulator.set_eval_mode(EvalMode::Incremental);
+        simulator
+            .add_breakpoint("mem.data".parse().unwrap())
+            .unwrap();
+
+        let json = serde_json::to_string(&simulator.checkpoint()).unwrap();
+        let mut restored_clock = 0;
+        let mut restored =
+            Simulator::restore(&Checkpoint::load(&json).unwrap(), &mut restored_clock).unwrap();
+        assert!(restored.four_state);
+        assert_eq!(restored.sim_state, simulator.sim_state);
+        assert_eq!(restored.format_input(&data), "0x00000000000000XX");
+        assert_eq!(restored.eval_mode, EvalMode::Incremental);
+        assert_eq!(restored.breakpoints, simulator.breakpoints);
+
+        // the byte written before the checkpoint is read back, hitting the
+        // restored breakpoint
+        restored.set_out_val("addr", "out", 4);
+        restored.clock(&mut restored_clock);
+        assert_eq!(restored.format_input(&data), "66");
+        assert_eq!(restored.hits.len(), 1);
+    }
+}
@@ -0,0 +1,556 @@
+// Command line interface, shared by SyncRim applications
+use crate::{
+    breakpoint::{Breakpoint, Hit},
+    common::{parse_signal, ComponentStore, EvalMode, Input, Signal, Simulator},
+    format::{self, FileFormat},
+    lint::{self, Problem, Severity},
+    migrate, schema,
+    trace::{Mismatch, Trace},
+};
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{fs, io::Write, path::PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// Path to the model to load on startup
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Simulate a model without GUI, printing outputs per cycle
+    Run(RunArgs),
+    /// Compare a simulation against a reference trace (VCD or CSV)
+    Compare(CompareArgs),
+    /// Check a model for problems (e.g., unknown inputs, unread outputs)
+    Check(CheckArgs),
+    /// Upgrade a model to the current format version
+    Convert(ConvertArgs),
+    /// Print the JSON Schema of model files, for editors
+    Schema(SchemaArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    /// Path to the model (or checkpoint with `--resume`)
+    pub model: PathBuf,
+
+    /// Resume from a checkpoint, given as the model
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Save a checkpoint when done
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Simulate until clock number `cycles` (the reset state is clock 1)
+    #[arg(short, long, default_value_t = 1)]
+    pub cycles: usize,
+
+    /// Stop early when the condition holds, e.g., `add.out=0x2a`
+    #[arg(short, long)]
+    pub until: Option<String>,
+
+    /// Stop on a breakpoint, `id.field=value`, `id.field>value`,
+    /// `id.field&mask=value`, `id.field` (changes) or `id@addr[:r|:w]` (memory)
+    #[arg(short, long = "break")]
+    pub breakpoint: Vec<String>,
+
+    /// Output to print, as `id.field` (all outputs if none given)
+    #[arg(short, long)]
+    pub probe: Vec<String>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
+
+    /// Maximum number of clocks kept in history (unlimited if not given)
+    #[arg(long)]
+    pub history: Option<usize>,
+
+    /// Evaluate only components with changed inputs
+    #[arg(long)]
+    pub incremental: bool,
+
+    /// Four-state signals, outputs are unknown (X) until set
+    #[arg(long)]
+    pub four_state: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CompareArgs {
+    /// Path to the model
+    pub model: PathBuf,
+
+    /// Path to the reference trace (`.vcd` or `.csv`)
+    pub reference: PathBuf,
+
+    /// Time of one clock cycle in a VCD reference
+    #[arg(long, default_value_t = 1)]
+    pub period: u64,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CheckArgs {
+    /// Path to the model
+    pub model: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ConvertArgs {
+    /// Path to the model (included models are converted separately)
+    pub model: PathBuf,
+
+    /// Path to write the converted model to (the model itself if not given,
+    /// `<name>.new.<ext>` for a RON or YAML file with comments), the format
+    /// is given by the extension (`json`, `ron`, `yaml` or `toml`)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SchemaArgs {
+    /// List the component types instead
+    #[arg(long)]
+    pub types: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+// a condition `id.field=value`
+struct Until {
+    input: Input,
+    value: Signal,
+}
+
+impl Until {
+    fn parse(text: &str) -> Result<Self> {
+        let (signal, value) = text.split_once('=').ok_or_else(|| {
+            anyhow!(
+                "Failed to parse condition {:?}, expected `id.field=value`",
+                text
+            )
+        })?;
+        Ok(Until {
+            input: signal.parse()?,
+            value: parse_signal(value)?,
+        })
+    }
+}
+
+/// run the model from `args` headless, writing the probed outputs to `out`,
+/// returns the breakpoints hit (if any)
+pub fn run(args: &RunArgs, out: &mut impl Write) -> Result<Vec<Hit>> {
+    if args.resume {
+        let mut clock = 0;
+        let simulator = Simulator::load_checkpoint(&args.model, &mut clock)?;
+        run_simulator(simulator, clock, args, out)
+    } else {
+        let cs = ComponentStore::load_file(&args.model)?;
+        run_store(&cs, args, out)
+    }
+}
+
+/// run the model `cs` headless, writing the probed outputs to `out`,
+/// returns the breakpoints hit (if any)
+pub fn run_store(cs: &ComponentStore, args: &RunArgs, out: &mut impl Write) -> Result<Vec<Hit>> {
+    let mut clock = 0;
+    let mut simulator = Simulator::new(cs, &mut clock)?;
+    if args.four_state {
+        simulator.set_four_state(true, &mut clock);
+    }
+    run_simulator(simulator, clock, args, out)
+}
+
+fn run_simulator(
+    mut simulator: Simulator,
+    mut clock: usize,
+    args: &RunArgs,
+    out: &mut impl Write,
+) -> Result<Vec<Hit>> {
+    if args.history.is_some() {
+        simulator.set_history_depth(args.history);
+    }
+    if args.incremental {
+        simulator.set_eval_mode(EvalMode::Incremental);
+    }
+    for breakpoint in &args.breakpoint {
+        // a resumed checkpoint may already hold the breakpoint
+        let breakpoint = breakpoint.parse::<Breakpoint>()?;
+        if !simulator.breakpoints.contains(&breakpoint) {
+            simulator.add_breakpoint(breakpoint)?;
+        }
+    }
+
+    let probes = if args.probe.is_empty() {
+        simulator.outputs()
+    } else {
+        args.probe
+            .iter()
+            .map(|p| p.parse())
+            .collect::<Result<Vec<Input>>>()?
+    };
+    let indexes = probes
+        .iter()
+        .map(|p| simulator.get_input_index(p))
+        .collect::<Result<Vec<usize>, _>>()?;
+    let until = args.until.as_deref().map(Until::parse).transpose()?;
+    let until_index = until
+        .as_ref()
+        .map(|u| simulator.get_input_index(&u.input))
+        .transpose()?;
+
+    let names: Vec<String> = probes.iter().map(|p| p.to_string()).collect();
+    let mut writer = Writer::new(args.format, &names);
+    writer.header(out)?;
+    loop {
+        let values: Vec<String> = indexes
+            .iter()
+            .map(|i| simulator.format_index(*i, simulator.sim_state[*i]))
+            .collect();
+        writer.row(out, clock, &values)?;
+
+        let done = match (&until, until_index) {
+            (Some(until), Some(index)) => {
+                simulator.sim_state[index] == until.value && simulator.get_xz(index) == 0
+            }
+            _ => false,
+        } || !simulator.hits.is_empty();
+        if done || clock >= args.cycles {
+            break;
+        }
+        simulator.clock(&mut clock);
+    }
+    writer.footer(out)?;
+    if let Some(path) = &args.checkpoint {
+        simulator.save_checkpoint(path)?;
+    }
+    Ok(simulator.hits)
+}
+
+/// compare the model against the reference trace, returns the number of
+/// compared clocks, or the first mismatch
+pub fn compare(args: &CompareArgs) -> Result<Result<usize, Mismatch>> {
+    let cs = ComponentStore::load_file(&args.model)?;
+    let trace = Trace::load_file(&args.reference, args.period)?;
+    let mut clock = 0;
+    let mut simulator = Simulator::new(&cs, &mut clock)?;
+    Ok(match simulator.compare_trace(&trace, &mut clock)? {
+        Some(mismatch) => Err(mismatch),
+        None => Ok(clock),
+    })
+}
+
+/// check the model for problems
+pub fn check(args: &CheckArgs) -> Result<Vec<Problem>> {
+    let cs = ComponentStore::load_file(&args.model)?;
+    Ok(lint::check(&cs))
+}
+
+/// upgrade the model to the current format version, keeping its parameters
+/// and includes, returns the version it had and the file written (another
+/// one for a RON or YAML file with comments, see `format`)
+pub fn convert(args: &ConvertArgs) -> Result<(u64, PathBuf)> {
+    let text = fs::read_to_string(&args.model)?;
+    let mut model = FileFormat::from_path(&args.model).parse(&text)?;
+    let version = migrate::upgrade(&mut model)?;
+    // the converted model must load
+    ComponentStore::load_model(model.clone(), &args.model)?;
+    let output = args.output.as_ref().unwrap_or(&args.model);
+    let written = format::write_file(output, &model)?;
+    Ok((version, written))
+}
+
+/// the JSON Schema of model files (see `schema`), or the component types
+pub fn schema(args: &SchemaArgs) -> Result<String> {
+    if args.types {
+        let names: Vec<&str> = schema::component_types().iter().map(|t| t.name).collect();
+        Ok(names.join("\n"))
+    } else {
+        Ok(serde_json::to_string_pretty(&schema::model_schema())?)
+    }
+}
+
+/// execute `command`, printing the result, returns the exit code
+pub fn execute(command: Command) -> i32 {
+    match command {
+        Command::Run(run_args) => {
+            // headless, stdout is reserved for the simulation output
+            match run(&run_args, &mut std::io::stdout().lock()) {
+                Ok(hits) => hits.iter().for_each(|hit| eprintln!("{}", hit)),
+                Err(err) => {
+                    eprintln!("error: {:#}", err);
+                    return 1;
+                }
+            }
+        }
+        Command::Compare(compare_args) => match compare(&compare_args) {
+            Ok(Ok(clock)) => println!("No mismatch, compared until clock {}", clock),
+            Ok(Err(mismatch)) => {
+                println!("{}", mismatch);
+                return 1;
+            }
+            Err(err) => {
+                eprintln!("error: {:#}", err);
+                return 2;
+            }
+        },
+        Command::Check(check_args) => match check(&check_args) {
+            Ok(problems) => {
+                problems.iter().for_each(|problem| println!("{}", problem));
+                if problems.is_empty() {
+                    println!("No problems found");
+                }
+                if problems.iter().any(|p| p.severity() == Severity::Error) {
+                    return 1;
+                }
+            }
+            Err(err) => {
+                eprintln!("error: {:#}", err);
+                return 2;
+            }
+        },
+        Command::Convert(convert_args) => match convert(&convert_args) {
+            Ok((version, written)) => println!(
+                "Converted {} from version {} to {}, written to {}",
+                convert_args.model.display(),
+                version,
+                migrate::VERSION,
+                written.display()
+            ),
+            Err(err) => {
+                eprintln!("error: {:#}", err);
+                return 1;
+            }
+        },
+        Command::Schema(schema_args) => match schema(&schema_args) {
+            Ok(text) => println!("{}", text),
+            Err(err) => {
+                eprintln!("error: {:#}", err);
+                return 1;
+            }
+        },
+    }
+    0
+}
+
+// formats the rows of a run
+struct Writer<'a> {
+    format: Format,
+    names: &'a [String],
+    widths: Vec<usize>,
+    first: bool,
+}
+
+impl<'a> Writer<'a> {
+    fn new(format: Format, names: &'a [String]) -> Self {
+        // room for the name, or a 10 digit (32 bit) value
+        let widths = names.iter().map(|n| n.len().max(10)).collect();
+        Writer {
+            format,
+            names,
+            widths,
+            first: true,
+        }
+    }
+
+    fn header(&self, out: &mut impl Write) -> Result<()> {
+        match self.format {
+            Format::Table => {
+                write!(out, "{:>10}", "cycle")?;
+                for (name, width) in self.names.iter().zip(&self.widths) {
+                    write!(out, " {:>width$}", name, width = width)?;
+                }
+                writeln!(out)?;
+            }
+            Format::Csv => writeln!(out, "cycle,{}", self.names.join(","))?,
+            Format::Json => writeln!(out, "[")?,
+        }
+        Ok(())
+    }
+
+    // values formatted by `format_signal`, unknown values (e.g., `X`) are
+    // given as JSON strings
+    fn row(&mut self, out: &mut impl Write, clock: usize, values: &[String]) -> Result<()> {
+        match self.format {
+            Format::Table => {
+                write!(out, "{:>10}", clock)?;
+                for (value, width) in values.iter().zip(&self.widths) {
+                    write!(out, " {:>width$}", value, width = width)?;
+                }
+                writeln!(out)?;
+            }
+            Format::Csv => {
+                write!(out, "{}", clock)?;
+                for value in values {
+                    write!(out, ",{}", value)?;
+                }
+                writeln!(out)?;
+            }
+            Format::Json => {
+                if !self.first {
+                    writeln!(out, ",")?;
+                }
+                write!(out, "  {{\"cycle\": {}", clock)?;
+                for (name, value) in self.names.iter().zip(values) {
+                    write!(out, ", {}: ", serde_json::to_string(name)?)?;
+                    match value.parse::<Signal>() {
+                        Ok(value) => write!(out, "{}", value)?,
+                        Err(_) => write!(out, "{}", serde_json::to_string(value)?)?,
+                    }
+                }
+                write!(out, "}}")?;
+            }
+        }
+        self.first = false;
+        Ok(())
+    }
+
+    fn footer(&self, out: &mut impl Write) -> Result<()> {
+        if self.format == Format::Json {
+            writeln!(out, "\n]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::counter;
+
+    fn args(format: Format, until: Option<&str>) -> RunArgs {
+        RunArgs {
+            model: PathBuf::new(),
+            resume: false,
+            checkpoint: None,
+            breakpoint: vec![],
+            cycles: 4,
+            until: until.map(|u| u.into()),
+            probe: vec!["add.out".into(), "reg.out".into()],
+            format,
+            history: Some(2),
+            incremental: false,
+            four_state: false,
+        }
+    }
+
+    fn run_to_string(args: &RunArgs) -> String {
+        let mut out = vec![];
+        run_store(&counter(1), args, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_run_csv() {
+        assert_eq!(
+            run_to_string(&args(Format::Csv, None)),
+            "cycle,add.out,reg.out\n1,1,0\n2,2,1\n3,3,2\n4,4,3\n"
+        );
+    }
+
+    #[test]
+    fn test_run_until() {
+        assert_eq!(
+            run_to_string(&args(Format::Json, Some("reg.out=0x1"))),
+            "[\n  {\"cycle\": 1, \"add.out\": 1, \"reg.out\": 0},\n  {\"cycle\": 2, \"add.out\": 2, \"reg.out\": 1}\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_run_break() {
+        let mut args = args(Format::Csv, None);
+        args.breakpoint.push("add.out>2".into());
+        let mut out = vec![];
+        let hits = run_store(&counter(1), &args, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "cycle,add.out,reg.out\n1,1,0\n2,2,1\n3,3,2\n"
+        );
+        assert_eq!(hits[0].to_string(), "Breakpoint add.out>2 hit at clock 3");
+    }
+
+    #[test]
+    fn test_run_four_state() {
+        // the register is never reset, so the counter stays unknown
+        let mut args = args(Format::Json, None);
+        args.four_state = true;
+        args.cycles = 2;
+        assert_eq!(
+            run_to_string(&args),
+            "[\n  {\"cycle\": 1, \"add.out\": \"X\", \"reg.out\": \"X\"},\n  {\"cycle\": 2, \"add.out\": \"X\", \"reg.out\": \"X\"}\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_run_unknown_probe() {
+        let mut args = args(Format::Table, None);
+        args.probe.push("reg.missing".into());
+        let mut out = vec![];
+        let err = run_store(&counter(1), &args, &mut out).unwrap_err();
+        assert_eq!(err.to_string(), "Signal reg.missing not found");
+    }
+
+    #[test]
+    fn test_convert() {
+        // a version 0 model, `Add` without width
+        let path = std::env::temp_dir().join("syncrim_test_convert.json");
+        let json = r#"{"parameters": {"N": 1}, "store": [
+            {"type": "Constant", "id": "c", "pos": [0, 0], "value": "$N"},
+            {"type": "Add", "id": "add", "pos": [0, 0], "a_in": {"id": "c", "field": "out"}, "b_in": {"id": "c", "field": "out"}}
+        ]}"#;
+        fs::write(&path, json).unwrap();
+        let args = ConvertArgs {
+            model: path.clone(),
+            output: None,
+        };
+        assert_eq!(convert(&args).unwrap(), (0, path.clone()));
+        let converted: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(converted["version"], migrate::VERSION);
+        assert_eq!(converted["parameters"]["N"], 1);
+        assert_eq!(converted["store"][1]["width"], 32);
+        // the layout of the model is kept, added fields come last
+        let keys = |value: &serde_json::Value| -> Vec<String> {
+            value.as_object().unwrap().keys().cloned().collect()
+        };
+        assert_eq!(keys(&converted), ["parameters", "store", "version"]);
+        assert_eq!(
+            keys(&converted["store"][1]),
+            ["type", "id", "pos", "a_in", "b_in", "width"]
+        );
+        assert_eq!(convert(&args).unwrap().0, migrate::VERSION);
+
+        // a YAML model with comments is converted to another file
+        let path = std::env::temp_dir().join("syncrim_test_convert.yaml");
+        fs::write(
+            &path,
+            "# a constant\nstore: [{type: Constant, id: c, pos: [0, 0], value: 1}]\n",
+        )
+        .unwrap();
+        let args = ConvertArgs {
+            model: path.clone(),
+            output: None,
+        };
+        let written = std::env::temp_dir().join("syncrim_test_convert.new.yaml");
+        assert_eq!(convert(&args).unwrap(), (0, written.clone()));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("# a constant"));
+        let converted = FileFormat::Yaml
+            .parse(&fs::read_to_string(&written).unwrap())
+            .unwrap();
+        assert_eq!(converted["version"], migrate::VERSION);
+    }
+}
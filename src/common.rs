@@ -1,13 +1,20 @@
+use crate::breakpoint::{Breakpoint, Hit};
+use crate::history::History;
+use anyhow::anyhow;
 use petgraph::Graph;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::rc::Rc;
+use std::{any::Any, fmt, path::PathBuf, rc::Rc, str::FromStr};
 
 #[cfg(feature = "gui-vizia")]
 use vizia::prelude::*;
 
-pub type Signal = u32;
-pub type SignedSignal = i32;
+pub use syncrim_derive::{component, ComponentPorts, ComponentSchema};
+
+// Signals are up to 64 bits, narrower signals are given by port widths
+pub type Signal = u64;
+pub type SignedSignal = i64;
 pub type Id = String;
 
 #[cfg(not(any(feature = "gui-vizia", feature = "gui-egui")))]
@@ -25,16 +32,49 @@ pub struct Simulator {
     pub id_start_index: IdStartIndex,
 
     // Components stored in topological evaluation order
+    // the first `nr_sequential` components are sequential
     pub ordered_components: Components,
+    pub nr_sequential: usize,
+    // Outputs of sequential components, buffered during the sample phase
+    pub sampled: Option<Vec<(usize, Signal)>>,
     pub sim_state: Vec<Signal>,
+    // Width of each `sim_state` index, and the mask applied when set
+    pub widths: Vec<Width>,
+    pub masks: Vec<Signal>,
     pub id_nr_outputs: IdNrOutputs,
     pub id_field_index: IdFieldIndex,
-    pub history: Vec<Vec<Signal>>,
+    pub history: History,
     pub component_ids: Vec<Id>,
-    pub graph: Graph<Id, ()>,
+    // Indexes in `sim_state` of the inputs of each component (in
+    // `ordered_components` order), resolved when building the simulator
+    pub input_index: Vec<Vec<usize>>,
+    // Start index in `sim_state` of the outputs of each component
+    pub output_index: Vec<usize>,
+    // The component being evaluated (index in `ordered_components`)
+    pub current: usize,
+    pub eval_mode: EvalMode,
+    // Combinatorial components (index in `ordered_components`) reading
+    // each `sim_state` index, from the edges of `graph`
+    pub fanout: Vec<Vec<usize>>,
+    // Components with a changed input, to be evaluated (incremental mode)
+    pub dirty: Vec<bool>,
+    // Four-state (0, 1, X, Z) signals, the unknown bits of output `i` are
+    // kept at `sim_state[widths.len() + i]`, X where the value bit is 1, Z
+    // where it is 0 (see `Simulator::set_four_state`)
+    pub four_state: bool,
+    pub graph: Graph<Id, Id>,
+    pub breakpoints: Vec<Breakpoint>,
+    // Breakpoints hit by the last clock
+    pub hits: Vec<Hit>,
+    // The sub-circuit enclosing each component of a flattened sub-circuit
+    pub parents: HashMap<Id, Id>,
+    // Combinatorial loops of a model built for viewing (see
+    // `Simulator::view`), such a model is not evaluated
+    pub loops: Vec<CombinatorialLoop>,
 }
 
-#[derive(Serialize, Deserialize)]
+// Serialized with the format version (see `migrate`)
+#[derive(Deserialize)]
 pub struct ComponentStore {
     pub store: Components,
 }
@@ -49,15 +89,42 @@ pub type IdStartIndex = HashMap<Id, usize>;
 
 pub type IdNrOutputs = HashMap<Id, usize>;
 
-pub type IdFieldIndex = HashMap<(Id, Id), usize>;
+// a mapping (id -> field -> index)
+// where index is relative to the start index of the component
+pub type IdFieldIndex = HashMap<Id, HashMap<Id, usize>>;
+
+// The ports of a component, usually derived (`#[derive(ComponentPorts)]`)
+// from the fields marked `#[input]` and `#[inputs]`, see `syncrim-derive`
+pub trait ComponentPorts {
+    /// returns the (id, Ports) of the component
+    fn id_ports(&self) -> (Id, Ports);
+
+    /// the position of the component, the field `pos` if any
+    fn pos(&self) -> Option<(f32, f32)> {
+        None
+    }
+}
+
+// Access to the concrete type of a component (e.g., for the checks of
+// `lint`), implemented for all types
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
 
 // Common functionality for all components
 #[typetag::serde(tag = "type")]
-pub trait Component {
+pub trait Component: AsAny {
     // placeholder
     fn to_(&self) {}
 
-    /// returns the (id, Ports) of the component
+    /// returns the (id, Ports) of the component, usually the derived
+    /// `ComponentPorts::id_ports` given by `#[component]`
     fn get_id_ports(&self) -> (Id, Ports);
 
     /// evaluate component based on current internal state
@@ -65,6 +132,47 @@ pub trait Component {
 
     /// update component internal state
     fn un_clock(&self) {}
+
+    /// reset component internal state
+    fn reset(&self) {}
+
+    /// forget the internal state kept to un_clock all but the last `depth`
+    /// clocks, as older clocks are dropped from history (see
+    /// `Simulator::set_history_depth`)
+    fn forget_older(&self, _depth: usize) {}
+
+    /// re-evaluate the current clock (see `Simulator::settle`), by default
+    /// reverting the internal state of the clock and evaluating again
+    fn settle(&self, simulator: &mut Simulator) {
+        self.un_clock();
+        self.clock(simulator);
+    }
+
+    /// true if the component must be evaluated on every clock, e.g., due
+    /// to side effects, internal state or inputs not listed in its ports,
+    /// otherwise it is evaluated only on input changes (in incremental mode)
+    fn always_evaluate(&self) -> bool {
+        false
+    }
+
+    /// the components replacing this component when building the simulator
+    /// (see `ComponentStore::flatten`), e.g., a `SubCircuit` followed by the
+    /// components of its circuit, `None` if simulated as is
+    fn flatten(&self) -> Option<Result<ComponentStore, SimError>> {
+        None
+    }
+
+    /// the ids of the components of a flattened sub-circuit
+    fn sub_components(&self) -> Vec<Id> {
+        vec![]
+    }
+
+    /// the position of the component, for highlighting it (e.g., as a
+    /// member of a combinatorial loop), `None` if not shown (given by the
+    /// derived `ComponentPorts::pos` with `#[component]`)
+    fn pos(&self) -> Option<(f32, f32)> {
+        None
+    }
 }
 
 // Specific functionality for Vizia frontend
@@ -93,24 +201,72 @@ pub trait EguiComponent: Component {
     }
 }
 
+// The bit width of a port, `None` for any width (up to `Signal::BITS`)
+pub type Width = Option<u32>;
+
 #[derive(Debug, Clone)]
 pub struct Ports {
     pub inputs: Vec<Input>,
+    // width of each input, in `inputs` order
+    pub input_widths: Vec<Width>,
     pub out_type: OutputType,
     pub outputs: Vec<Id>,
+    // width of each output, in `outputs` order, outputs are masked to
+    // their width
+    pub output_widths: Vec<Width>,
 }
 
 impl Ports {
+    /// ports of any width
     pub fn new(inputs: Vec<&Input>, out_type: OutputType, outputs: Vec<&str>) -> Self {
         Ports {
+            input_widths: vec![None; inputs.len()],
             inputs: inputs.into_iter().cloned().collect(),
             out_type,
+            output_widths: vec![None; outputs.len()],
             outputs: outputs.into_iter().map(|s| s.into()).collect(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// the mask of a signal of `width` bits
+pub fn width_mask(width: Width) -> Signal {
+    match width {
+        Some(width) if width < Signal::BITS => (1 << width) - 1,
+        _ => Signal::MAX,
+    }
+}
+
+/// format a signal of `width` bits with unknown bits `xz`, in decimal if
+/// known, `X` (`Z`) if all bits are X (Z), otherwise in hex with an `X`
+/// (`Z`) for each digit with unknown bits
+pub fn format_signal(value: Signal, xz: Signal, width: Width) -> String {
+    let mask = width_mask(width);
+    let (value, xz) = (value & mask, xz & mask);
+    if xz == 0 {
+        return value.to_string();
+    } else if xz == mask && value == mask {
+        return "X".into();
+    } else if xz == mask && value == 0 {
+        return "Z".into();
+    }
+    let digits = width.unwrap_or(Signal::BITS).min(Signal::BITS).div_ceil(4);
+    let mut text = String::from("0x");
+    for digit in (0..digits).rev() {
+        let shift = 4 * digit;
+        let (value, xz) = ((value >> shift) & 0xf, (xz >> shift) & 0xf);
+        text.push(if xz == 0 {
+            char::from_digit(value as u32, 16).unwrap()
+        } else if value & xz != 0 {
+            'X'
+        } else {
+            'Z'
+        });
+    }
+    text
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Input {
     pub id: Id,
     pub field: Id,
@@ -125,6 +281,48 @@ impl Input {
     }
 }
 
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.id, self.field)
+    }
+}
+
+impl FromStr for Input {
+    type Err = anyhow::Error;
+
+    // parse `id.field`, the id may itself contain dots
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().rsplit_once('.') {
+            Some((id, field)) if !id.is_empty() && !field.is_empty() => Ok(Input::new(id, field)),
+            _ => Err(anyhow!("Failed to parse {:?}, expected `id.field`", s)),
+        }
+    }
+}
+
+// parse a signal value given in decimal or hex (`0x` prefix)
+pub fn parse_signal(text: &str) -> Result<Signal, anyhow::Error> {
+    let text = text.trim();
+
+    if let Ok(signal) = text.parse::<Signal>() {
+        Ok(signal)
+    } else if let Some(hex) = text.strip_prefix("0x") {
+        let signal = Signal::from_str_radix(hex, 16)?;
+        Ok(signal)
+    } else {
+        Err(anyhow!("Failed to parse {}", text))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum EvalMode {
+    // Evaluate all components on each clock
+    #[default]
+    Full,
+    // Evaluate only combinatorial components with a changed input, giving
+    // the same result as the full evaluation
+    Incremental,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum OutputType {
     // Will be evaluated as a combinatorial function from inputs to outputs
@@ -140,3 +338,154 @@ pub enum Output {
     // Will be evaluated as a function
     Function,
 }
+
+// A combinatorial loop, given as the outputs closing the loop,
+// where each output drives the component of the next one
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombinatorialLoop(pub Vec<Input>);
+
+impl CombinatorialLoop {
+    // ids of the components in the loop
+    pub fn ids(&self) -> impl Iterator<Item = &Id> {
+        self.0.iter().map(|input| &input.id)
+    }
+}
+
+impl fmt::Display for CombinatorialLoop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for input in &self.0 {
+            write!(f, "{}.{} -> ", input.id, input.field)?;
+        }
+        write!(f, "{}", self.0[0].id)
+    }
+}
+
+// Errors reported when loading, saving or building a model
+#[derive(Debug)]
+pub enum SimError {
+    // Two components share the same identifier
+    DuplicateId(Id),
+    // Two components of a model and its includes share the same identifier,
+    // defined in files `first` and `second`
+    DuplicateIncludedId {
+        id: Id,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    // A component declares the same output field twice
+    DuplicateField {
+        id: Id,
+        field: Id,
+    },
+    // Component `id` has an input referring to a non-existing component
+    UnknownComponent {
+        id: Id,
+        input: Input,
+    },
+    // Component `id` has an input referring to a non-existing field
+    UnknownField {
+        id: Id,
+        input: Input,
+    },
+    // The combinatorial part of the model contains loops
+    Loop(Vec<CombinatorialLoop>),
+    // Component `id` has an input driven by a wider output
+    Width {
+        id: Id,
+        input: Input,
+        width: u32,
+        input_width: u32,
+    },
+    // Component `id` declares output `field` of a width outside
+    // 1 ..= Signal::BITS
+    OutputWidth {
+        id: Id,
+        field: Id,
+        width: u32,
+    },
+    // A signal (component output) that does not exist in the model
+    UnknownSignal(Input),
+    // A component identifier that does not exist in the model
+    UnknownId(Id),
+    // The model could not be parsed, `path` locates the offending element
+    Parse {
+        path: String,
+        msg: String,
+    },
+    // The model could not be serialized
+    Serialize(String),
+    // Reading or writing `path` failed
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::DuplicateId(id) => {
+                write!(f, "Component identifier {:?} is defined twice", id)
+            }
+            SimError::DuplicateIncludedId { id, first, second } => write!(
+                f,
+                "Component identifier {:?} is defined twice, in {:?} and {:?}",
+                id, first, second
+            ),
+            SimError::DuplicateField { id, field } => {
+                write!(f, "Component {:?} field {:?} is defined twice", id, field)
+            }
+            SimError::UnknownComponent { id, input } => write!(
+                f,
+                "Component {:?} has input from unknown component {:?}",
+                id, input.id
+            ),
+            SimError::UnknownField { id, input } => write!(
+                f,
+                "Component {:?} has input from unknown field {:?} of component {:?}",
+                id, input.field, input.id
+            ),
+            SimError::Loop(loops) => {
+                write!(f, "Topological sort failed, your model contains loops:")?;
+                for l in loops {
+                    write!(f, "\n  {}", l)?;
+                }
+                Ok(())
+            }
+            SimError::Width {
+                id,
+                input,
+                width,
+                input_width,
+            } => write!(
+                f,
+                "Component {:?} has a {} bit input driven by {} of {} bits",
+                id, input_width, input, width
+            ),
+            SimError::OutputWidth { id, field, width } => write!(
+                f,
+                "Component {:?} has output {:?} of {} bits, expected 1 ..= {}",
+                id,
+                field,
+                width,
+                Signal::BITS
+            ),
+            SimError::UnknownSignal(input) => {
+                write!(f, "Signal {}.{} not found", input.id, input.field)
+            }
+            SimError::UnknownId(id) => write!(f, "Component {:?} not found", id),
+            SimError::Parse { path, msg } => write!(f, "Parse error at {}: {}", path, msg),
+            SimError::Serialize(msg) => write!(f, "Serialization failed: {}", msg),
+            SimError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for SimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SimError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
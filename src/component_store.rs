@@ -1,31 +1,115 @@
-use crate::common::ComponentStore;
+use crate::common::{ComponentStore, SimError};
+use crate::{
+    format::{self, FileFormat},
+    include, migrate,
+};
 
-use std::{fs::File, io::prelude::*, path::PathBuf};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::prelude::*,
+    path::{Path, PathBuf},
+};
 
 use log::*;
 
 impl ComponentStore {
-    pub fn load(json: &str) -> Self {
-        serde_json::from_str(json).unwrap()
+    /// load a model, resolving its parameters (see `parameters`) and
+    /// includes (see `include`, relative to the current directory)
+    pub fn load(json: &str) -> Result<Self, SimError> {
+        ComponentStore::load_model(FileFormat::Json.parse(json)?, Path::new(""))
     }
 
-    pub fn load_file(path: &PathBuf) -> Self {
-        let mut file = File::open(path).unwrap();
-        let mut json = String::new();
-        file.read_to_string(&mut json).unwrap();
+    /// load a model, in the format given by the extension (see `format`)
+    pub fn load_file(path: &PathBuf) -> Result<Self, SimError> {
+        let io_err = |error| SimError::Io {
+            path: path.to_owned(),
+            error,
+        };
+        let mut file = File::open(path).map_err(io_err)?;
+        let mut text = String::new();
+        file.read_to_string(&mut text).map_err(io_err)?;
 
-        ComponentStore::load(&json)
+        ComponentStore::load_model(FileFormat::from_path(path).parse(&text)?, path)
     }
 
-    pub fn save_file(&self, path: &PathBuf) {
-        let json = serde_json::to_string(self).unwrap();
-        trace!("json: {}", json);
+    // load `model` of `file`
+    pub(crate) fn load_model(model: Value, file: &Path) -> Result<Self, SimError> {
+        let canonical = fs::canonicalize(file).unwrap_or_else(|_| file.into());
+        let model = include::load(model, file, Map::new(), &mut vec![canonical])?;
+
+        // the ids of the included components, checked here to report the files
+        if model.origins.iter().any(|(origin, _)| origin != file) {
+            let mut defined: HashMap<&str, &Path> = HashMap::new();
+            let ids = model.value["store"].as_array().into_iter().flatten();
+            for (c, (origin, _)) in ids.zip(&model.origins) {
+                let id = c["id"].as_str().unwrap_or_default();
+                if let Some(first) = defined.insert(id, origin) {
+                    return Err(SimError::DuplicateIncludedId {
+                        id: id.into(),
+                        first: first.into(),
+                        second: origin.clone(),
+                    });
+                }
+            }
+        }
+
+        serde_path_to_error::deserialize(model.value).map_err(|err| {
+            let path = err.path().to_string();
+            // locate the component in the file it came from
+            let origin = path
+                .strip_prefix("store[")
+                .and_then(|rest| rest.split_once(']'))
+                .and_then(|(index, rest)| {
+                    Some((model.origins.get(index.parse::<usize>().ok()?)?, rest))
+                });
+            SimError::Parse {
+                path: match origin {
+                    Some(((origin, index), rest)) if origin != file => {
+                        format!("{}: store[{}]{}", origin.display(), index, rest)
+                    }
+                    _ => path,
+                },
+                msg: err.inner().to_string(),
+            }
+        })
+    }
+
+    /// save the model, in the format given by the extension (see `format`),
+    /// returns the file written (another one for a RON or YAML file with
+    /// comments)
+    pub fn save_file(&self, path: &Path) -> Result<PathBuf, SimError> {
+        let model = serde_json::to_value(self).map_err(|e| SimError::Serialize(e.to_string()))?;
+        let path = format::write_file(path, &model)?;
         trace!("path {:?}", path);
-        let mut file = File::create(path).unwrap();
-        file.write_all(json.as_bytes()).unwrap();
+        Ok(path)
+    }
+
+    /// replace the components built from other components (e.g., a
+    /// `SubCircuit`) by these (see `Component::flatten`), recursively
+    pub fn flatten(&self) -> Result<ComponentStore, SimError> {
+        let mut store = vec![];
+        for c in &self.store {
+            match c.flatten() {
+                Some(flattened) => store.extend(flattened?.flatten()?.store),
+                None => store.push(c.clone()),
+            }
+        }
+        Ok(ComponentStore { store })
     }
 
     pub fn to_(&self) {
         self.store.iter().for_each(|c| c.to_());
     }
 }
+
+impl Serialize for ComponentStore {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut model = serializer.serialize_struct("ComponentStore", 2)?;
+        model.serialize_field("version", &migrate::VERSION)?;
+        model.serialize_field("store", &self.store)?;
+        model.end()
+    }
+}
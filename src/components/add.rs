@@ -1,41 +1,45 @@
-use crate::common::{Component, Id, Input, OutputType, Ports, Signal, SignedSignal, Simulator};
+use crate::common::{
+    component, width_mask, Component, ComponentPorts, ComponentSchema, Id, Input, Signal,
+    SignedSignal, Simulator,
+};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(out(width = self.width), overflow(width = 1))]
 pub struct Add {
     pub id: Id,
     pub pos: (f32, f32),
+    #[input]
     pub a_in: Input,
+    #[input]
     pub b_in: Input,
+    // width of the (signed) addition, 1 ..= Signal::BITS (the width of
+    // `out`, checked when building the simulator)
+    pub width: u32,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for Add {
     fn to_(&self) {
         trace!("Add");
     }
 
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(
-                vec![&self.a_in, &self.b_in],
-                OutputType::Combinatorial,
-                vec!["out", "overflow"],
-            ),
-        )
-    }
-
     // propagate addition to output
     fn clock(&self, simulator: &mut Simulator) {
         // get input values
-        let a_in = simulator.get_input_val(&self.a_in);
-        let b_in = simulator.get_input_val(&self.b_in);
+        let a_in = simulator.get_input(0);
+        let b_in = simulator.get_input(1);
 
-        // compute addition (notice will panic on overflow)
-        let (value, overflow) =
-            SignedSignal::overflowing_add(a_in as SignedSignal, b_in as SignedSignal);
+        // sign extend the inputs from `width` bits
+        let shift = Signal::BITS - self.width;
+        let sext = |value: Signal| ((value << shift) as SignedSignal) >> shift;
+
+        // compute addition, overflowing if the sum does not fit in `width` bits
+        let (value, overflow) = SignedSignal::overflowing_add(sext(a_in), sext(b_in));
+        let overflow = overflow || sext(value as Signal) != value;
 
         trace!(
             "eval Add a_in {}, b_in {}, value = {}, overflow = {}",
@@ -45,8 +49,17 @@ impl Component for Add {
             overflow
         );
 
+        // in four-state mode, an unknown input bit makes the sum unknown from
+        // that bit up (through the carries), and the overflow unknown
+        let xz =
+            (simulator.get_input_xz(0) | simulator.get_input_xz(1)) & width_mask(Some(self.width));
+        let (xz, overflow_xz) = match xz {
+            0 => (0, 0),
+            xz => (Signal::MAX << xz.trailing_zeros(), 1),
+        };
+
         // set output
-        simulator.set_out_val(&self.id, "out", value as Signal);
-        simulator.set_out_val(&self.id, "overflow", Signal::from(overflow));
+        Add::set_out_xz(simulator, value as Signal | xz, xz);
+        Add::set_overflow_xz(simulator, Signal::from(overflow) | overflow_xz, overflow_xz);
     }
 }
@@ -1,11 +1,21 @@
-use crate::common::{Component, Id, Input, OutputType, Ports, Signal, Simulator};
+use crate::breakpoint::Access;
+use crate::common::{
+    component, Component, ComponentPorts, ComponentSchema, Id, Input, Signal, SignedSignal,
+    Simulator,
+};
 use log::*;
 use num_enum::IntoPrimitive;
 use num_enum::TryFromPrimitive;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, collections::HashMap, convert::TryFrom};
-
-#[derive(Serialize, Deserialize)]
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    convert::TryFrom,
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(data, err(width = 1))]
 pub struct Mem {
     pub id: Id,
     pub pos: (f32, f32),
@@ -16,20 +26,32 @@ pub struct Mem {
     pub big_endian: bool,
 
     // ports
+    #[input]
     pub data: Input,
+    #[input]
     pub addr: Input,
+    #[input]
     pub ctrl: Input,
+    #[input]
     pub sign: Input,
+    #[input]
     pub size: Input,
 
     // memory
     pub memory: Memory,
-    // later history... tbd
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Memory {
     bytes: RefCell<HashMap<usize, u8>>,
+    // undo log, (clock, addr, previous byte) for each byte written
+    #[serde(skip)]
+    log: RefCell<Vec<(usize, usize, Option<u8>)>>,
+    // the initial bytes of the writes forgotten from the undo log
+    #[serde(skip)]
+    initial: RefCell<HashMap<usize, Option<u8>>>,
+    #[serde(skip)]
+    clock: Cell<usize>,
 }
 
 impl Default for Memory {
@@ -42,11 +64,67 @@ impl Memory {
     pub fn new() -> Self {
         Memory {
             bytes: RefCell::new(HashMap::new()),
+            log: RefCell::new(vec![]),
+            initial: RefCell::new(HashMap::new()),
+            clock: Cell::new(0),
+        }
+    }
+
+    // advance the clock of the undo log
+    fn clock(&self) {
+        self.clock.set(self.clock.get() + 1);
+    }
+
+    // revert the writes of the current clock
+    fn un_clock(&self) {
+        let clock = self.clock.get();
+        let mut log = self.log.borrow_mut();
+        while matches!(log.last(), Some((c, _, _)) if *c == clock) {
+            let (_, addr, byte) = log.pop().unwrap();
+            self.restore(addr, byte);
+        }
+        self.clock.set(clock.saturating_sub(1));
+    }
+
+    // revert all writes, restoring the initial content
+    fn reset(&self) {
+        for (_, addr, byte) in self.log.borrow_mut().drain(..).rev() {
+            self.restore(addr, byte);
+        }
+        for (addr, byte) in self.initial.borrow_mut().drain() {
+            self.restore(addr, byte);
         }
+        self.clock.set(0);
+    }
+
+    // forget the writes of all but the last `depth` clocks, keeping the
+    // initial bytes for reset
+    fn forget_older(&self, depth: usize) {
+        let oldest = self.clock.get().saturating_sub(depth);
+        let mut log = self.log.borrow_mut();
+        let forgotten = log.partition_point(|(clock, _, _)| *clock <= oldest);
+        let mut initial = self.initial.borrow_mut();
+        for (_, addr, byte) in log.drain(..forgotten) {
+            initial.entry(addr).or_insert(byte);
+        }
+    }
+
+    fn restore(&self, addr: usize, byte: Option<u8>) {
+        let mut bytes = self.bytes.borrow_mut();
+        match byte {
+            Some(byte) => bytes.insert(addr, byte),
+            None => bytes.remove(&addr),
+        };
+    }
+
+    // write a byte, logging the previous value
+    fn store(&self, addr: usize, byte: u8) {
+        let old = self.bytes.borrow_mut().insert(addr, byte);
+        self.log.borrow_mut().push((self.clock.get(), addr, old));
     }
 
     fn align(&self, addr: usize, size: usize) -> Signal {
-        (addr % size != 0) as Signal
+        (!addr.is_multiple_of(size)) as Signal
     }
 
     fn read(&self, addr: usize, size: usize, sign: bool, big_endian: bool) -> Signal {
@@ -54,64 +132,37 @@ impl Memory {
             .map(|i| *self.bytes.borrow().get(&(addr + i)).unwrap_or(&0))
             .collect();
 
-        let data = data.as_slice();
-
         trace!("{:x?}", data);
-        match size {
-            1 => {
-                if sign {
-                    data[0] as i8 as Signal
-                } else {
-                    data[0] as Signal
-                }
-            }
-            2 => {
-                if sign {
-                    if big_endian {
-                        trace!("read signed half word be");
-                        let i_16 = i16::from_be_bytes(data.try_into().unwrap());
-                        trace!("i_16 {:x?}", i_16);
-                        let i_32 = i_16 as i32;
-                        trace!("i_32 {:x?}", i_32);
-                        i_32 as Signal
-                    } else {
-                        trace!("read signed half word le");
-                        let i_16 = i16::from_le_bytes(data.try_into().unwrap());
-                        trace!("i_16 {:x?}", i_16);
-                        let i_32 = i_16 as i32;
-                        trace!("i_32 {:x?}", i_32);
-                        i_32 as Signal
-                    }
-                } else if big_endian {
-                    trace!("read unsigned half word be");
-                    let u_16 = u16::from_be_bytes(data.try_into().unwrap());
-                    trace!("u_16 {:x?}", u_16);
-                    let u_32 = u_16 as u32;
-                    trace!("u_32 {:x?}", u_32);
-                    u_32 as Signal
-                } else {
-                    trace!("read unsigned half word le");
-                    let u_16 = u16::from_le_bytes(data.try_into().unwrap());
-                    trace!("u_16 {:x?}", u_16);
-                    let u_32 = u_16 as u32;
-                    trace!("u_32 {:x?}", u_32);
-                    u_32 as Signal
-                }
-            }
-            4 => {
-                if sign {
-                    if big_endian {
-                        i32::from_be_bytes(data.try_into().unwrap()) as Signal
-                    } else {
-                        i32::from_le_bytes(data.try_into().unwrap()) as Signal
-                    }
-                } else if big_endian {
-                    u32::from_be_bytes(data.try_into().unwrap()) as Signal
+        decode(&data, size, sign, big_endian)
+    }
+
+    // the bits of a read from undefined (never written) bytes, extended
+    // as the read value
+    fn undefined(&self, addr: usize, size: usize, sign: bool, big_endian: bool) -> Signal {
+        let data: Vec<u8> = (0..size)
+            .map(|i| {
+                if self.bytes.borrow().contains_key(&(addr + i)) {
+                    0
                 } else {
-                    u32::from_le_bytes(data.try_into().unwrap()) as Signal
+                    0xff
                 }
+            })
+            .collect();
+
+        decode(&data, size, sign, big_endian)
+    }
+
+    // make the bytes written with unknown bits `xz` undefined, logging the
+    // previous values
+    fn write_unknown(&self, addr: usize, size: usize, big_endian: bool, xz: Signal) {
+        for i in 0..size {
+            let shift = 8 * if big_endian { size - 1 - i } else { i };
+            if (xz >> shift) & 0xff != 0 {
+                let old = self.bytes.borrow_mut().remove(&(addr + i));
+                self.log
+                    .borrow_mut()
+                    .push((self.clock.get(), addr + i, old));
             }
-            _ => panic!("illegal sized memory operation"),
         }
     }
 
@@ -119,7 +170,7 @@ impl Memory {
         match size {
             1 => {
                 trace!("write byte");
-                self.bytes.borrow_mut().insert(addr, data as u8);
+                self.store(addr, data as u8);
             }
             2 => {
                 if big_endian {
@@ -129,7 +180,7 @@ impl Memory {
                         .iter()
                         .enumerate()
                         .for_each(|(i, bytes)| {
-                            self.bytes.borrow_mut().insert(addr + i, *bytes);
+                            self.store(addr + i, *bytes);
                         })
                 } else {
                     trace!("write half word le");
@@ -138,7 +189,7 @@ impl Memory {
                         .iter()
                         .enumerate()
                         .for_each(|(i, bytes)| {
-                            self.bytes.borrow_mut().insert(addr + i, *bytes);
+                            self.store(addr + i, *bytes);
                         })
                 }
             }
@@ -146,19 +197,40 @@ impl Memory {
             4 => {
                 if big_endian {
                     trace!("write word be");
-                    data.to_be_bytes()
+                    (data as u32)
+                        .to_be_bytes()
                         .iter()
                         .enumerate()
                         .for_each(|(i, bytes)| {
-                            self.bytes.borrow_mut().insert(addr + i, *bytes);
+                            self.store(addr + i, *bytes);
                         })
                 } else {
                     trace!("write word le");
+                    (data as u32)
+                        .to_le_bytes()
+                        .iter()
+                        .enumerate()
+                        .for_each(|(i, bytes)| {
+                            self.store(addr + i, *bytes);
+                        })
+                }
+            }
+            8 => {
+                if big_endian {
+                    trace!("write double word be");
+                    data.to_be_bytes()
+                        .iter()
+                        .enumerate()
+                        .for_each(|(i, bytes)| {
+                            self.store(addr + i, *bytes);
+                        })
+                } else {
+                    trace!("write double word le");
                     data.to_le_bytes()
                         .iter()
                         .enumerate()
                         .for_each(|(i, bytes)| {
-                            self.bytes.borrow_mut().insert(addr + i, *bytes);
+                            self.store(addr + i, *bytes);
                         })
                 }
             }
@@ -169,6 +241,74 @@ impl Memory {
     }
 }
 
+// decode `size` bytes of `data`, sign extended to the 64 bits of the
+// output if `sign` (for sizes up to 4 bytes)
+fn decode(data: &[u8], size: usize, sign: bool, big_endian: bool) -> Signal {
+    match size {
+        1 => {
+            if sign {
+                data[0] as i8 as SignedSignal as Signal
+            } else {
+                data[0] as Signal
+            }
+        }
+        2 => {
+            if sign {
+                if big_endian {
+                    trace!("read signed half word be");
+                    let i_16 = i16::from_be_bytes(data.try_into().unwrap());
+                    trace!("i_16 {:x?}", i_16);
+                    let i_64 = i_16 as SignedSignal;
+                    trace!("i_64 {:x?}", i_64);
+                    i_64 as Signal
+                } else {
+                    trace!("read signed half word le");
+                    let i_16 = i16::from_le_bytes(data.try_into().unwrap());
+                    trace!("i_16 {:x?}", i_16);
+                    let i_64 = i_16 as SignedSignal;
+                    trace!("i_64 {:x?}", i_64);
+                    i_64 as Signal
+                }
+            } else if big_endian {
+                trace!("read unsigned half word be");
+                let u_16 = u16::from_be_bytes(data.try_into().unwrap());
+                trace!("u_16 {:x?}", u_16);
+                let u_32 = u_16 as u32;
+                trace!("u_32 {:x?}", u_32);
+                u_32 as Signal
+            } else {
+                trace!("read unsigned half word le");
+                let u_16 = u16::from_le_bytes(data.try_into().unwrap());
+                trace!("u_16 {:x?}", u_16);
+                let u_32 = u_16 as u32;
+                trace!("u_32 {:x?}", u_32);
+                u_32 as Signal
+            }
+        }
+        4 => {
+            if sign {
+                if big_endian {
+                    i32::from_be_bytes(data.try_into().unwrap()) as SignedSignal as Signal
+                } else {
+                    i32::from_le_bytes(data.try_into().unwrap()) as SignedSignal as Signal
+                }
+            } else if big_endian {
+                u32::from_be_bytes(data.try_into().unwrap()) as Signal
+            } else {
+                u32::from_le_bytes(data.try_into().unwrap()) as Signal
+            }
+        }
+        8 => {
+            if big_endian {
+                u64::from_be_bytes(data.try_into().unwrap())
+            } else {
+                u64::from_le_bytes(data.try_into().unwrap())
+            }
+        }
+        _ => panic!("illegal sized memory operation"),
+    }
+}
+
 #[derive(Copy, Clone, Debug, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)] // Unfortunately Rust does not allow Signal here, we need to cast manually
 pub enum MemCtrl {
@@ -177,24 +317,22 @@ pub enum MemCtrl {
     Write,
 }
 
+#[component]
 #[typetag::serde()]
 impl Component for Mem {
     fn to_(&self) {
         trace!("Mem");
     }
 
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(
-                vec![&self.data, &self.addr, &self.ctrl],
-                OutputType::Combinatorial,
-                vec!["data", "err"],
-            ),
-        )
-    }
-
     fn clock(&self, simulator: &mut Simulator) {
+        self.memory.clock();
+        // unknown (X/Z) address or control inputs give unknown outputs,
+        // without accessing the memory
+        if (1..5).any(|port| simulator.get_input_xz(port) != 0) {
+            Mem::set_data_xz(simulator, Signal::MAX, Signal::MAX);
+            Mem::set_err_xz(simulator, Signal::MAX, Signal::MAX);
+            return;
+        }
         let data = simulator.get_input_val(&self.data);
         let addr = simulator.get_input_val(&self.addr) as usize;
         let ctrl = MemCtrl::try_from(simulator.get_input_val(&self.ctrl) as u8).unwrap();
@@ -204,18 +342,31 @@ impl Component for Mem {
         match ctrl {
             MemCtrl::Read => {
                 trace!("read addr {:?} size {:?}", addr, size);
+                simulator.watch_address(&self.id, addr, size, Access::Read);
                 let value = self.memory.read(addr, size, sign, self.big_endian);
-                simulator.set_out_val(&self.id, "data", value);
+                // undefined bytes read as unknown (X) in four-state mode
+                let xz = if simulator.four_state {
+                    self.memory.undefined(addr, size, sign, self.big_endian)
+                } else {
+                    0
+                };
+                Mem::set_data_xz(simulator, value | xz, xz);
                 let value = self.memory.align(addr, size);
                 trace!("align {}", value);
-                simulator.set_out_val(&self.id, "err", value); // align
+                Mem::set_err_xz(simulator, value, 0); // align
             }
             MemCtrl::Write => {
                 trace!("write addr {:?} size {:?}", addr, size);
+                simulator.watch_address(&self.id, addr, size, Access::Write);
                 self.memory.write(addr, size, self.big_endian, data);
+                // bytes written with unknown bits become undefined
+                let xz = simulator.get_input_xz(0);
+                if xz != 0 {
+                    self.memory.write_unknown(addr, size, self.big_endian, xz);
+                }
                 let value = self.memory.align(addr, size);
                 trace!("align {}", value);
-                simulator.set_out_val(&self.id, "err", value); // align
+                Mem::set_err_xz(simulator, value, 0); // align
             }
             MemCtrl::None => {
                 trace!("no read/write");
@@ -224,6 +375,23 @@ impl Component for Mem {
 
         trace!("memory {:?}", self.memory);
     }
+
+    fn un_clock(&self) {
+        self.memory.un_clock();
+    }
+
+    fn reset(&self) {
+        self.memory.reset();
+    }
+
+    fn forget_older(&self, depth: usize) {
+        self.memory.forget_older(depth);
+    }
+
+    // accesses are reported to watchpoints, and the undo log is clocked
+    fn always_evaluate(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -259,15 +427,13 @@ mod test {
                     sign: Input::new("sign", "out"),
 
                     // memory
-                    memory: Memory {
-                        bytes: RefCell::new(HashMap::new()),
-                    },
+                    memory: Memory::new(),
                 }),
             ],
         };
 
         let mut clock = 0;
-        let mut simulator = Simulator::new(&cs, &mut clock);
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
 
         assert_eq!(clock, 1);
 
@@ -312,7 +478,7 @@ mod test {
 
         simulator.clock(&mut clock);
         assert_eq!(clock, 4);
-        assert_eq!(simulator.get_input_val(out), 0xffff_fff0);
+        assert_eq!(simulator.get_input_val(out), 0xffff_ffff_ffff_fff0);
         assert_eq!(simulator.get_input_val(err), false as Signal);
 
         println!("<setup for read half-word from addr 4>");
@@ -321,7 +487,7 @@ mod test {
 
         simulator.clock(&mut clock);
         assert_eq!(clock, 5);
-        assert_eq!(simulator.get_input_val(out), 0xffff_f000);
+        assert_eq!(simulator.get_input_val(out), 0xffff_ffff_ffff_f000);
         assert_eq!(simulator.get_input_val(err), false as Signal);
 
         println!("<setup for read word from addr 4>");
@@ -330,7 +496,7 @@ mod test {
 
         simulator.clock(&mut clock);
         assert_eq!(clock, 6);
-        assert_eq!(simulator.get_input_val(out), 0xf000_0000);
+        assert_eq!(simulator.get_input_val(out), 0xffff_ffff_f000_0000);
         assert_eq!(simulator.get_input_val(err), false as Signal);
 
         println!("<setup for read word from addr 5>");
@@ -425,16 +591,13 @@ mod test {
                     sign: Input::new("sign", "out"),
 
                     // memory
-                    memory: Memory {
-                        bytes: RefCell::new(HashMap::new()),
-                    },
-                    // later history... tbd
+                    memory: Memory::new(),
                 }),
             ],
         };
 
         let mut clock = 0;
-        let mut simulator = Simulator::new(&cs, &mut clock);
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
 
         assert_eq!(clock, 1);
 
@@ -480,7 +643,7 @@ mod test {
 
         simulator.clock(&mut clock);
         assert_eq!(clock, 4);
-        assert_eq!(simulator.get_input_val(out), 0xffff_fff0);
+        assert_eq!(simulator.get_input_val(out), 0xffff_ffff_ffff_fff0);
         assert_eq!(simulator.get_input_val(err), false as Signal);
 
         println!("<setup for read half-word from addr 4>");
@@ -523,4 +686,332 @@ mod test {
         assert_eq!(clock, 9);
         assert_eq!(simulator.get_input_val(out), 0x12 as Signal);
     }
+
+    #[test]
+    fn test_mem_un_clock() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("data")),
+                Rc::new(ProbeOut::new("addr")),
+                Rc::new(ProbeOut::new("ctrl")),
+                Rc::new(ProbeOut::new("size")),
+                Rc::new(ProbeOut::new("sign")),
+                Rc::new(Mem {
+                    id: "mem".into(),
+                    pos: (0.0, 0.0),
+                    width: 0.0,
+                    height: 0.0,
+
+                    // configuration
+                    big_endian: true,
+
+                    // ports
+                    data: Input::new("data", "out"),
+                    addr: Input::new("addr", "out"),
+                    ctrl: Input::new("ctrl", "out"),
+                    size: Input::new("size", "out"),
+                    sign: Input::new("sign", "out"),
+
+                    // memory
+                    memory: Memory::new(),
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        let out = &Input::new("mem", "data");
+
+        // write word 0x12345678 at addr 4, then overwrite byte at addr 5
+        simulator.set_out_val("data", "out", 0x1234_5678);
+        simulator.set_out_val("addr", "out", 4);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.set_out_val("size", "out", 4);
+        simulator.clock(&mut clock);
+        simulator.set_out_val("data", "out", 0xff);
+        simulator.set_out_val("addr", "out", 5);
+        simulator.set_out_val("size", "out", 1);
+        simulator.clock(&mut clock);
+        assert_eq!(clock, 3);
+
+        // read word at addr 4
+        simulator.set_out_val("addr", "out", 4);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.set_out_val("size", "out", 4);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 0x12ff_5678);
+
+        // step back before the byte write, and read again
+        simulator.un_clock(&mut clock);
+        simulator.un_clock(&mut clock);
+        assert_eq!(clock, 2);
+        simulator.set_out_val("addr", "out", 4);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.set_out_val("size", "out", 4);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 0x1234_5678);
+
+        // reset restores the initial (empty) memory
+        simulator.reset(&mut clock);
+        simulator.set_out_val("addr", "out", 4);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.set_out_val("size", "out", 4);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 0);
+    }
+
+    #[test]
+    fn test_mem_forget() {
+        let mem = Rc::new(Mem {
+            id: "mem".into(),
+            pos: (0.0, 0.0),
+            width: 0.0,
+            height: 0.0,
+            big_endian: true,
+            data: Input::new("data", "out"),
+            addr: Input::new("addr", "out"),
+            ctrl: Input::new("ctrl", "out"),
+            size: Input::new("size", "out"),
+            sign: Input::new("sign", "out"),
+            memory: Memory::new(),
+        });
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("data")),
+                Rc::new(ProbeOut::new("addr")),
+                Rc::new(ProbeOut::new("ctrl")),
+                Rc::new(ProbeOut::new("size")),
+                Rc::new(ProbeOut::new("sign")),
+                mem.clone(),
+            ],
+        };
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_history_depth(Some(4));
+        let out = &Input::new("mem", "data");
+
+        // a byte store every clock, only the last clocks are kept in the log
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.set_out_val("size", "out", 1);
+        for addr in 0..100 {
+            simulator.set_out_val("addr", "out", addr % 10);
+            simulator.set_out_val("data", "out", addr + 1);
+            simulator.clock(&mut clock);
+        }
+        assert_eq!(mem.memory.log.borrow().len(), 4);
+        assert_eq!(mem.memory.initial.borrow().len(), 10);
+
+        // the kept clocks are reverted
+        simulator.un_clock(&mut clock);
+        simulator.set_out_val("addr", "out", 9);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 90);
+
+        // reset restores the initial (empty) memory
+        simulator.reset(&mut clock);
+        simulator.set_out_val("addr", "out", 9);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.set_out_val("size", "out", 1);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 0);
+    }
+
+    #[test]
+    fn test_mem_double() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("data")),
+                Rc::new(ProbeOut::new("addr")),
+                Rc::new(ProbeOut::new("ctrl")),
+                Rc::new(ProbeOut::new("size")),
+                Rc::new(ProbeOut::new("sign")),
+                Rc::new(Mem {
+                    id: "mem".into(),
+                    pos: (0.0, 0.0),
+                    width: 0.0,
+                    height: 0.0,
+
+                    // configuration
+                    big_endian: true,
+
+                    // ports
+                    data: Input::new("data", "out"),
+                    addr: Input::new("addr", "out"),
+                    ctrl: Input::new("ctrl", "out"),
+                    size: Input::new("size", "out"),
+                    sign: Input::new("sign", "out"),
+
+                    // memory
+                    memory: Memory::new(),
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        let out = &Input::new("mem", "data");
+
+        // write double word at addr 8
+        simulator.set_out_val("data", "out", 0x8765_4321_1234_5678);
+        simulator.set_out_val("addr", "out", 8);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.set_out_val("size", "out", 8);
+        simulator.clock(&mut clock);
+
+        // read double word
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 0x8765_4321_1234_5678);
+
+        // signed reads are extended to the 64 bits of the output
+        simulator.set_out_val("size", "out", 4);
+        simulator.set_out_val("sign", "out", true as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 0xffff_ffff_8765_4321);
+
+        // -1 of any size reads as -1
+        simulator.set_out_val("data", "out", Signal::MAX);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.set_out_val("size", "out", 8);
+        simulator.clock(&mut clock);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        for size in [1, 2, 4] {
+            simulator.set_out_val("size", "out", size);
+            simulator.clock(&mut clock);
+            assert_eq!(simulator.get_input_val(out) as SignedSignal, -1);
+        }
+        simulator.set_out_val("sign", "out", false as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(out), 0xffff_ffff);
+    }
+
+    #[test]
+    fn test_mem_four_state() {
+        // byte 0 is defined, the other bytes are undefined
+        let memory = Memory::new();
+        memory.bytes.borrow_mut().insert(0, 0x12);
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("data")),
+                Rc::new(ProbeOut::new("addr")),
+                Rc::new(ProbeOut::new("ctrl")),
+                Rc::new(ProbeOut::new("size")),
+                Rc::new(ProbeOut::new("sign")),
+                Rc::new(Mem {
+                    id: "mem".into(),
+                    pos: (0.0, 0.0),
+                    width: 0.0,
+                    height: 0.0,
+
+                    // configuration
+                    big_endian: false,
+
+                    // ports
+                    data: Input::new("data", "out"),
+                    addr: Input::new("addr", "out"),
+                    ctrl: Input::new("ctrl", "out"),
+                    size: Input::new("size", "out"),
+                    sign: Input::new("sign", "out"),
+
+                    // memory
+                    memory,
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_four_state(true, &mut clock);
+        let out = &Input::new("mem", "data");
+        assert_eq!(simulator.format_input(out), "X");
+
+        // unknown address
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.set_out_val("size", "out", 2);
+        simulator.set_out_val("sign", "out", false as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(out), "X");
+
+        // undefined byte 1 reads as X, also when sign extended
+        simulator.set_out_val("addr", "out", 0);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(out), "0x000000000000XX12");
+        simulator.set_out_val("sign", "out", true as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(out), "0xXXXXXXXXXXXXXX12");
+        simulator.set_out_val("size", "out", 1);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(out), "18");
+
+        // writing unknown data makes byte 0 undefined
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(&Input::new("mem", "err")), "0");
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.set_out_val("sign", "out", false as Signal);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(out), "0x00000000000000XX");
+    }
+
+    #[test]
+    fn test_mem_watch() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("data")),
+                Rc::new(ProbeOut::new("addr")),
+                Rc::new(ProbeOut::new("ctrl")),
+                Rc::new(ProbeOut::new("size")),
+                Rc::new(ProbeOut::new("sign")),
+                Rc::new(Mem {
+                    id: "mem".into(),
+                    pos: (0.0, 0.0),
+                    width: 0.0,
+                    height: 0.0,
+
+                    // configuration
+                    big_endian: true,
+
+                    // ports
+                    data: Input::new("data", "out"),
+                    addr: Input::new("addr", "out"),
+                    ctrl: Input::new("ctrl", "out"),
+                    size: Input::new("size", "out"),
+                    sign: Input::new("sign", "out"),
+
+                    // memory
+                    memory: Memory::new(),
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator
+            .add_breakpoint("mem@0x6:w".parse().unwrap())
+            .unwrap();
+
+        // write word at addr 4, covering addr 6
+        simulator.set_out_val("addr", "out", 4);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.set_out_val("size", "out", 4);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.hits.len(), 1);
+        assert_eq!(
+            simulator.hits[0].to_string(),
+            "Breakpoint mem@0x6:w hit at clock 2"
+        );
+
+        // read of addr 6, and write of addr 7, do not hit
+        simulator.set_out_val("addr", "out", 6);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Read as Signal);
+        simulator.set_out_val("size", "out", 1);
+        simulator.clock(&mut clock);
+        assert!(simulator.hits.is_empty());
+        simulator.set_out_val("addr", "out", 7);
+        simulator.set_out_val("ctrl", "out", MemCtrl::Write as Signal);
+        simulator.clock(&mut clock);
+        assert!(simulator.hits.is_empty());
+    }
 }
@@ -7,6 +7,7 @@ mod probe_edit;
 mod probe_out;
 mod register;
 mod sext;
+mod sub_circuit;
 mod wire;
 
 pub use add::*;
@@ -18,4 +19,5 @@ pub use probe_edit::*;
 pub use probe_out::*;
 pub use register::*;
 pub use sext::*;
+pub use sub_circuit::*;
 pub use wire::*;
@@ -1,43 +1,59 @@
-use crate::common::{Component, Id, Input, OutputType, Ports, Simulator};
+use crate::common::{
+    component, width_mask, Component, ComponentPorts, ComponentSchema, Id, Input, Signal, Simulator,
+};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(out)]
 pub struct Mux {
     pub id: Id,
     pub pos: (f32, f32),
+    // any width, only the low `select_width()` bits address `m_in` (a wider
+    // select is reported by `lint::check`)
+    #[input]
     pub select: Input,
+    #[inputs]
     pub m_in: Vec<Input>,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for Mux {
     fn to_(&self) {
         trace!("mux");
     }
 
-    fn get_id_ports(&self) -> (Id, Ports) {
-        let mut inputs = vec![self.select.clone()];
-        let mut m = self.m_in.clone();
-        inputs.append(&mut m);
-
-        (
-            self.id.clone(),
-            Ports {
-                inputs,
-                out_type: OutputType::Combinatorial,
-                outputs: vec!["out".into()],
-            },
-        )
-    }
-
     // propagate selected input value to output
     fn clock(&self, simulator: &mut Simulator) {
+        // an unknown select gives an unknown (X) output
+        let mask = width_mask(Some(self.select_width()));
+        if simulator.get_input_xz(0) & mask != 0 {
+            Mux::set_out_xz(simulator, Signal::MAX, Signal::MAX);
+            return;
+        }
+
         // get input value
-        let select = simulator.get_input_val(&self.select) as usize;
+        let select = (simulator.get_input(0) & mask) as usize;
         trace!("select {}", select);
-        let value = simulator.get_input_val(&self.m_in[select]);
+        assert!(
+            select < self.m_in.len(),
+            "{}: select {} out of range for {} inputs",
+            self.id,
+            select,
+            self.m_in.len()
+        );
+        let value = simulator.get_input(1 + select);
+        let xz = simulator.get_input_xz(1 + select);
 
         // set output
-        simulator.set_out_val(&self.id, "out", value);
+        Mux::set_out_xz(simulator, value, xz);
+    }
+}
+
+impl Mux {
+    /// the width of the select, addressing all of `m_in`
+    pub fn select_width(&self) -> u32 {
+        (usize::BITS - self.m_in.len().saturating_sub(1).leading_zeros()).max(1)
     }
 }
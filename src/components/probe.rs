@@ -1,29 +1,19 @@
-use crate::common::{Component, Id, Input, OutputType, Ports};
+use crate::common::{component, Component, ComponentPorts, ComponentSchema, Id, Input};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
 pub struct Probe {
     pub id: Id,
     pub pos: (f32, f32),
+    #[input]
     pub input: Input,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for Probe {
     fn to_(&self) {
         trace!("Probe");
     }
-
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(
-                // Probes take one input
-                vec![&self.input],
-                OutputType::Combinatorial,
-                // No output value
-                vec![],
-            ),
-        )
-    }
 }
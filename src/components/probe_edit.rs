@@ -1,47 +1,37 @@
-use crate::common::{Component, Id, OutputType, Ports, Signal, Simulator};
+use crate::common::{component, Component, ComponentPorts, ComponentSchema, Id, Signal, Simulator};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, RwLock};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, ComponentSchema, ComponentPorts)]
+#[outputs(out)]
 pub struct ProbeEdit {
     pub id: Id,
     pub pos: (f32, f32),
     pub history: Arc<RwLock<Vec<TextSignal>>>, // will contain the next editable value
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct TextSignal {
     pub text: String,
     pub signal: Signal,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for ProbeEdit {
     fn to_(&self) {
         trace!("ProbeEdit");
     }
 
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(
-                // Constants do not take any inputs
-                vec![],
-                OutputType::Combinatorial,
-                // Single output value
-                vec!["out"],
-            ),
-        )
-    }
-
     // propagate editable value
     fn clock(&self, simulator: &mut Simulator) {
         let mut history = self.history.write().unwrap();
         trace!("{} history {:?}", self.id, history);
         let current = history.last().unwrap().clone();
         // set output to current value
-        simulator.set_out_val(&self.id, "out", current.signal);
+        ProbeEdit::set_out(simulator, current.signal);
         // push to prepare data for next;
         history.push(current);
     }
@@ -59,6 +49,20 @@ impl Component for ProbeEdit {
         history.push(prev.clone()); // push as current
         history.push(prev); // push as next (to be edited)
     }
+
+    // the edited (next) value becomes the current value
+    fn settle(&self, simulator: &mut Simulator) {
+        let mut history = self.history.write().unwrap();
+        let next = history.pop().unwrap();
+        *history.last_mut().unwrap() = next.clone();
+        ProbeEdit::set_out(simulator, next.signal);
+        history.push(next);
+    }
+
+    // the value is edited outside of the simulation
+    fn always_evaluate(&self) -> bool {
+        true
+    }
 }
 
 impl ProbeEdit {
@@ -1,30 +1,20 @@
-use crate::common::{Component, Id, OutputType, Ports};
+use crate::common::{component, Component, ComponentPorts, ComponentSchema, Id};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(out)]
 pub struct ProbeOut {
     pub id: Id,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for ProbeOut {
     fn to_(&self) {
         trace!("ProbeOut");
     }
-
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(
-                // Constants do not take any inputs
-                vec![],
-                OutputType::Combinatorial,
-                // Single output value
-                vec!["out"],
-            ),
-        )
-    }
 }
 
 impl ProbeOut {
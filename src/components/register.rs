@@ -1,37 +1,30 @@
-use crate::common::{Component, Id, Input, OutputType, Ports, Simulator};
+use crate::common::{component, Component, ComponentPorts, ComponentSchema, Id, Input, Simulator};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(out)]
+#[sequential]
 pub struct Register {
     pub id: Id,
     pub pos: (f32, f32),
+    #[input]
     pub r_in: Input,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for Register {
     fn to_(&self) {
         trace!("register");
     }
 
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(
-                // Vector of inputs
-                vec![&self.r_in],
-                OutputType::Sequential,
-                vec!["out"],
-            ),
-        )
-    }
-
     // propagate input value to output
     fn clock(&self, simulator: &mut Simulator) {
         // get input value
-        let value = simulator.get_input_val(&self.r_in);
+        let value = simulator.get_input(0);
         // set output
-        simulator.set_out_val(&self.id, "out", value);
+        Register::set_out(simulator, value);
         trace!("eval: register id {} in {}", self.id, value);
     }
 }
@@ -1,29 +1,30 @@
 // use std::fmt::Alignment;
-use crate::common::{Component, Id, Input, OutputType, Ports, Signal, SignedSignal, Simulator};
+use crate::common::{
+    component, Component, ComponentPorts, ComponentSchema, Id, Input, Signal, SignedSignal,
+    Simulator,
+};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+#[outputs(out(width = self.out_size))]
 pub struct Sext {
     pub id: Id,
     pub pos: (f32, f32),
+    // any width, the `in_size` least significant bits are extended
+    #[input]
     pub sext_in: Input,
     pub in_size: u32,
     pub out_size: u32,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for Sext {
     fn to_(&self) {
         trace!("Sign Extension");
     }
 
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(vec![&self.sext_in], OutputType::Combinatorial, vec!["out"]),
-        )
-    }
-
     // propagate sign extension to output
     // TODO: always extend to Signal size? (it should not matter and should be slightly cheaper)
     fn clock(&self, simulator: &mut Simulator) {
@@ -36,17 +37,22 @@ impl Component for Sext {
         );
 
         // get input values
-        let mut value = simulator.get_input_val(&self.sext_in);
+        let value = simulator.get_input(0);
 
         let to_sext = self.out_size - self.in_size; // Amount to be arithmetically shifted
         let to_shl = Signal::BITS - self.in_size; // To move input to MSB
         let to_shr = to_shl - to_sext; // To shift the result back to LSB
+        let sext = |mut value: Signal| {
+            value <<= to_shl;
+            value = (value as SignedSignal >> to_sext) as Signal;
+            value >> to_shr
+        };
 
-        value <<= to_shl;
-        value = (value as SignedSignal >> to_sext) as Signal;
-        value >>= to_shr;
+        // in four-state mode, the unknown bits are extended as the value, an
+        // unknown sign bit makes the extension unknown
+        let xz = sext(simulator.get_input_xz(0));
 
         // set output
-        simulator.set_out_val(&self.id, "out", value);
+        Sext::set_out_xz(simulator, sext(value) | xz, xz);
     }
 }
@@ -0,0 +1,400 @@
+// A sub-circuit, a model (a file or embedded) used as a component
+//
+// When building the simulator the sub-circuit is flattened (see
+// `Component::flatten`). The components of the circuit are added with their
+// ids prefixed by the id of the sub-circuit (e.g., `alu.add`) and their
+// positions relative to the sub-circuit. The input ports are components of
+// the circuit (e.g., a `ProbeEdit`, so that the circuit can be simulated on
+// its own), replaced by the inputs connected to the sub-circuit. The outputs
+// of the sub-circuit pass on outputs of the circuit.
+use crate::common::{
+    component, Component, ComponentSchema, ComponentStore, Id, Input, OutputType, Ports, SimError,
+    Simulator,
+};
+use crate::include::Rename;
+use log::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashSet, path::PathBuf};
+
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema)]
+pub struct SubCircuit {
+    pub id: Id,
+    pub pos: (f32, f32),
+    pub width: f32,
+    pub height: f32,
+    // the circuit, taken when flattened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit: Option<Circuit>,
+    pub inputs: Vec<SubInput>,
+    pub outputs: Vec<SubOutput>,
+    // the ids of the components of the circuit, once flattened
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<Id>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub enum Circuit {
+    // a model file, relative to the file of the model containing the
+    // sub-circuit (to the current directory for a model not loaded from a
+    // file), made absolute when loaded
+    File(PathBuf),
+    // an embedded model
+    Store(ComponentStore),
+}
+
+// input port `port`, the component of the circuit replaced by `input`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct SubInput {
+    pub port: Id,
+    pub input: Input,
+}
+
+// output `name`, passing on `output` of the circuit
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct SubOutput {
+    pub name: Id,
+    pub output: Input,
+}
+
+#[component]
+#[typetag::serde]
+impl Component for SubCircuit {
+    fn to_(&self) {
+        trace!("SubCircuit");
+    }
+
+    // the ports are given by the outputs, as the connected inputs are read
+    // by the components of the circuit
+    fn get_id_ports(&self) -> (Id, Ports) {
+        let inputs: Vec<Input> = self.outputs.iter().map(|o| self.inner(&o.output)).collect();
+        (
+            self.id.clone(),
+            Ports::new(
+                inputs.iter().collect(),
+                OutputType::Combinatorial,
+                self.outputs.iter().map(|o| o.name.as_str()).collect(),
+            ),
+        )
+    }
+
+    fn pos(&self) -> Option<(f32, f32)> {
+        Some(self.pos)
+    }
+
+    // pass on the outputs of the circuit
+    fn clock(&self, simulator: &mut Simulator) {
+        for port in 0..self.outputs.len() {
+            let value = simulator.get_input(port);
+            let xz = simulator.get_input_xz(port);
+            simulator.set_output_xz(port, value, xz);
+        }
+    }
+
+    fn flatten(&self) -> Option<Result<ComponentStore, SimError>> {
+        self.circuit.as_ref().map(|circuit| self.expand(circuit))
+    }
+
+    fn sub_components(&self) -> Vec<Id> {
+        self.components.clone()
+    }
+}
+
+impl SubCircuit {
+    // `input` of the circuit, as flattened, an input port (passed straight
+    // through to an output) is replaced by the connected input
+    fn inner(&self, input: &Input) -> Input {
+        match self.inputs.iter().find(|i| i.port == input.id) {
+            Some(port) => port.input.clone(),
+            None => Input::new(&format!("{}.{}", self.id, input.id), &input.field),
+        }
+    }
+
+    // the sub-circuit (without the circuit, listing its components),
+    // followed by the components of the circuit
+    fn expand(&self, circuit: &Circuit) -> Result<ComponentStore, SimError> {
+        let loaded;
+        let store = match circuit {
+            Circuit::File(path) => {
+                loaded = ComponentStore::load_file(path)?;
+                &loaded
+            }
+            Circuit::Store(store) => store,
+        };
+        let serialize = |err: serde_json::Error| SimError::Serialize(err.to_string());
+        let mut sub = serde_json::to_value(self as &dyn Component).map_err(serialize)?;
+        let mut circuit = serde_json::to_value(store).map_err(serialize)?;
+
+        let rename = Rename {
+            prefix: &self.id,
+            offset: self.pos,
+            ids: None,
+            ports: self
+                .inputs
+                .iter()
+                .map(|i| (i.port.as_str(), &i.input))
+                .collect(),
+        };
+        let mut replaced = HashSet::new();
+        let mut components = vec![];
+        if let Value::Array(store) = circuit["store"].take() {
+            for mut c in store {
+                let id = c["id"].as_str().unwrap_or_default().to_string();
+                if rename.ports.contains_key(id.as_str()) {
+                    replaced.insert(id);
+                } else {
+                    rename.component(&mut c);
+                    components.push(c);
+                }
+            }
+        }
+        if let Some(i) = self.inputs.iter().find(|i| !replaced.contains(&i.port)) {
+            return Err(SimError::UnknownId(format!("{}.{}", self.id, i.port)));
+        }
+
+        if let Value::Object(sub) = &mut sub {
+            sub.remove("circuit");
+            let ids: Vec<Value> = components.iter().map(|c| c["id"].clone()).collect();
+            sub.insert("components".into(), ids.into());
+        }
+        components.insert(0, sub);
+        let store = serde_json::json!({ "store": components });
+        serde_path_to_error::deserialize(store).map_err(|err| SimError::Parse {
+            path: format!("{}.{}", self.id, err.path()),
+            msg: err.inner().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::components::*;
+    use std::rc::Rc;
+
+    // an incrementer, `a` is the input port
+    fn inc() -> ComponentStore {
+        ComponentStore {
+            store: vec![
+                Rc::new(ProbeEdit::new("a", (0.0, 0.0))),
+                Rc::new(Constant {
+                    id: "one".into(),
+                    pos: (10.0, 20.0),
+                    value: 1,
+                }),
+                Rc::new(Add {
+                    id: "add".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("a", "out"),
+                    b_in: Input::new("one", "out"),
+                    width: 32,
+                }),
+            ],
+        }
+    }
+
+    fn sub_circuit(id: &str, circuit: Circuit, input: Input) -> Rc<SubCircuit> {
+        Rc::new(SubCircuit {
+            id: id.into(),
+            pos: (100.0, 0.0),
+            width: 40.0,
+            height: 40.0,
+            circuit: Some(circuit),
+            inputs: vec![SubInput {
+                port: "a".into(),
+                input,
+            }],
+            outputs: vec![SubOutput {
+                name: "out".into(),
+                output: Input::new("add", "out"),
+            }],
+            components: vec![],
+        })
+    }
+
+    #[test]
+    fn test_sub_circuit() {
+        // two instances, `inc2` twice nested, reg.out = inc2.out delayed
+        let twice = ComponentStore {
+            store: vec![
+                Rc::new(ProbeEdit::new("a", (0.0, 0.0))),
+                sub_circuit("i1", Circuit::Store(inc()), Input::new("a", "out")),
+                sub_circuit("i2", Circuit::Store(inc()), Input::new("i1", "out")),
+            ],
+        };
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("inc2", "out"),
+                }),
+                sub_circuit("inc1", Circuit::Store(inc()), Input::new("reg", "out")),
+                Rc::new(SubCircuit {
+                    id: "inc2".into(),
+                    pos: (200.0, 0.0),
+                    width: 40.0,
+                    height: 40.0,
+                    circuit: Some(Circuit::Store(twice)),
+                    inputs: vec![SubInput {
+                        port: "a".into(),
+                        input: Input::new("inc1", "out"),
+                    }],
+                    outputs: vec![SubOutput {
+                        name: "out".into(),
+                        output: Input::new("i2", "out"),
+                    }],
+                    components: vec![],
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        for _ in 0..3 {
+            simulator.clock(&mut clock);
+        }
+        // reg counts by 3
+        assert_eq!(simulator.get_input_val(&Input::new("reg", "out")), 9);
+        assert_eq!(simulator.get_input_val(&Input::new("inc1", "out")), 10);
+        assert_eq!(
+            simulator.get_input_val(&Input::new("inc2.i1.add", "out")),
+            11
+        );
+        assert_eq!(simulator.get_input_val(&Input::new("inc2", "out")), 12);
+
+        // the input ports are replaced
+        assert!(simulator
+            .get_input_index(&Input::new("inc1.a", "out"))
+            .is_err());
+        assert_eq!(simulator.enclosing("inc1.add"), vec!["inc1"]);
+        assert_eq!(simulator.enclosing("inc2.i2.one"), vec!["inc2.i2", "inc2"]);
+        assert!(simulator.enclosing("inc2").is_empty());
+
+        // positions are relative to the sub-circuit
+        let one = simulator
+            .ordered_components
+            .iter()
+            .find(|c| c.get_id_ports().0 == "inc2.i1.one")
+            .map(|c| serde_json::to_value(&**c).unwrap())
+            .unwrap();
+        assert_eq!(one["pos"], serde_json::json!([310.0, 20.0]));
+
+        // a checkpoint holds the flattened model
+        let checkpoint = simulator.checkpoint();
+        let restored = Simulator::restore(&checkpoint, &mut clock).unwrap();
+        assert_eq!(restored.get_input_val(&Input::new("inc2", "out")), 12);
+        assert_eq!(restored.parents, simulator.parents);
+    }
+
+    #[test]
+    fn test_sub_circuit_file() {
+        let path = std::env::temp_dir().join("syncrim_test_inc.json");
+        inc().save_file(&path).unwrap();
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    value: 41,
+                }),
+                sub_circuit("inc", Circuit::File(path), Input::new("c", "out")),
+            ],
+        };
+        let mut clock = 0;
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
+        assert_eq!(simulator.get_input_val(&Input::new("inc", "out")), 42);
+
+        // an input port passed straight through to an output
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    value: 41,
+                }),
+                Rc::new(SubCircuit {
+                    id: "through".into(),
+                    pos: (100.0, 0.0),
+                    width: 40.0,
+                    height: 40.0,
+                    circuit: Some(Circuit::Store(inc())),
+                    inputs: vec![SubInput {
+                        port: "a".into(),
+                        input: Input::new("c", "out"),
+                    }],
+                    outputs: vec![
+                        SubOutput {
+                            name: "a".into(),
+                            output: Input::new("a", "out"),
+                        },
+                        SubOutput {
+                            name: "out".into(),
+                            output: Input::new("add", "out"),
+                        },
+                    ],
+                    components: vec![],
+                }),
+            ],
+        };
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
+        assert_eq!(simulator.get_input_val(&Input::new("through", "a")), 41);
+        assert_eq!(simulator.get_input_val(&Input::new("through", "out")), 42);
+
+        // files relative to the model file, `twice` of `lib/twice.json`
+        // doubling `inc` of `lib/inc.json`
+        let dir = std::env::temp_dir().join("syncrim_test_sub_circuit");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        inc().save_file(&dir.join("lib/inc.json")).unwrap();
+        let twice = ComponentStore {
+            store: vec![
+                Rc::new(ProbeEdit::new("a", (0.0, 0.0))),
+                sub_circuit(
+                    "i",
+                    Circuit::File("inc.json".into()),
+                    Input::new("a", "out"),
+                ),
+                Rc::new(Add {
+                    id: "add".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("i", "out"),
+                    b_in: Input::new("i", "out"),
+                    width: 32,
+                }),
+            ],
+        };
+        twice.save_file(&dir.join("lib/twice.json")).unwrap();
+        let model = ComponentStore {
+            store: vec![
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    value: 20,
+                }),
+                sub_circuit(
+                    "twice",
+                    Circuit::File("lib/twice.json".into()),
+                    Input::new("c", "out"),
+                ),
+            ],
+        };
+        model.save_file(&dir.join("model.json")).unwrap();
+        let cs = ComponentStore::load_file(&dir.join("model.json")).unwrap();
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
+        assert_eq!(simulator.get_input_val(&Input::new("twice", "out")), 42);
+
+        // an input port missing in the circuit
+        let cs = ComponentStore {
+            store: vec![sub_circuit(
+                "inc",
+                Circuit::Store(ComponentStore { store: vec![] }),
+                Input::new("inc", "out"),
+            )],
+        };
+        assert_eq!(
+            Simulator::new(&cs, &mut clock).err().unwrap().to_string(),
+            "Component \"inc.a\" not found"
+        );
+    }
+}
@@ -1,30 +1,20 @@
-use crate::common::{Component, Id, Input, OutputType, Ports};
+use crate::common::{component, Component, ComponentPorts, ComponentSchema, Id, Input};
 use log::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
 pub struct Wire {
     pub id: Id,
     pub pos: (f32, f32),
     pub delta: (f32, f32),
+    #[input]
     pub input: Input,
 }
 
+#[component]
 #[typetag::serde]
 impl Component for Wire {
     fn to_(&self) {
         trace!("Wire");
     }
-
-    fn get_id_ports(&self) -> (Id, Ports) {
-        (
-            self.id.clone(),
-            Ports::new(
-                // Wires take one input
-                vec![&self.input],
-                OutputType::Combinatorial,
-                // No output value
-                vec![],
-            ),
-        )
-    }
 }
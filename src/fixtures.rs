@@ -0,0 +1,29 @@
+// Models shared by the tests of several modules
+use crate::common::{ComponentStore, Input, Signal};
+use crate::components::*;
+use std::rc::Rc;
+
+// counter, reg.out = add.out delayed one clock, counting by `c.out` (`inc`)
+pub fn counter(inc: Signal) -> ComponentStore {
+    ComponentStore {
+        store: vec![
+            Rc::new(Constant {
+                id: "c".into(),
+                pos: (0.0, 0.0),
+                value: inc,
+            }),
+            Rc::new(Register {
+                id: "reg".into(),
+                pos: (0.0, 0.0),
+                r_in: Input::new("add", "out"),
+            }),
+            Rc::new(Add {
+                id: "add".into(),
+                pos: (0.0, 0.0),
+                a_in: Input::new("c", "out"),
+                b_in: Input::new("reg", "out"),
+                width: 32,
+            }),
+        ],
+    }
+}
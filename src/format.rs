@@ -0,0 +1,435 @@
+// File formats of models, chosen by the file extension
+//
+// Models are loaded as JSON values (see `migrate`, `parameters` and
+// `include`), so each format is read into and written from a
+// `serde_json::Value`. JSON is written pretty-printed. RON, YAML and TOML
+// allow comments when editing a model by hand. The model written to a TOML
+// file with comments is merged into the existing document (`toml_edit`),
+// keeping the comments and layout of the items kept. RON and YAML models are
+// written anew, so a file with comments is not overwritten, the model is
+// written to another file (`<name>.new.<ext>`) instead.
+//
+// TOML integers are at most `i64::MAX`, larger values are written as
+// expressions of their hex value (`"$(0xffffffffffffffff)"`), resolved when
+// the model is loaded (see `parameters`).
+use crate::common::SimError;
+use log::*;
+use serde_json::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{DocumentMut, Item};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Json,
+    Ron,
+    Yaml,
+    Toml,
+}
+
+// Extensions of the formats, for file dialogs
+pub const EXTENSIONS: [&str; 5] = ["json", "ron", "yaml", "yml", "toml"];
+
+impl FileFormat {
+    /// the format of `path` by its extension, JSON if not known
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") => FileFormat::Ron,
+            Some("yaml" | "yml") => FileFormat::Yaml,
+            Some("toml") => FileFormat::Toml,
+            _ => FileFormat::Json,
+        }
+    }
+
+    /// parse `text` in this format
+    pub fn parse(self, text: &str) -> Result<Value, SimError> {
+        let parse_err = |msg: String| SimError::Parse {
+            path: ".".into(),
+            msg,
+        };
+        match self {
+            FileFormat::Json => {
+                let de = &mut serde_json::Deserializer::from_str(text);
+                serde_path_to_error::deserialize(de).map_err(|err| SimError::Parse {
+                    path: err.path().to_string(),
+                    msg: err.inner().to_string(),
+                })
+            }
+            FileFormat::Ron => ron::from_str(text).map_err(|e| parse_err(e.to_string())),
+            FileFormat::Yaml => serde_yaml::from_str(text).map_err(|e| parse_err(e.to_string())),
+            FileFormat::Toml => toml::from_str(text).map_err(|e| parse_err(e.to_string())),
+        }
+    }
+
+    /// write `value` in this format
+    pub fn write(self, value: &Value) -> Result<String, SimError> {
+        let serialize = |msg: String| SimError::Serialize(msg);
+        match self {
+            FileFormat::Json => {
+                serde_json::to_string_pretty(value).map_err(|e| serialize(e.to_string()))
+            }
+            FileFormat::Ron => ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new())
+                .map_err(|e| serialize(e.to_string())),
+            FileFormat::Yaml => serde_yaml::to_string(value).map_err(|e| serialize(e.to_string())),
+            FileFormat::Toml => {
+                let mut value = value.clone();
+                large_integers(&mut value);
+                toml::to_string_pretty(&value).map_err(|e| serialize(e.to_string()))
+            }
+        }
+    }
+
+    /// write `value` in this format, keeping the comments and layout of
+    /// `existing` (TOML only, `None` otherwise or if `existing` is not valid)
+    pub fn update(self, existing: &str, value: &Value) -> Option<String> {
+        if self != FileFormat::Toml {
+            return None;
+        }
+        let mut document: DocumentMut = existing.parse().ok()?;
+        let written: DocumentMut = self.write(value).ok()?.parse().ok()?;
+        merge(document.as_item_mut(), written.as_item().clone());
+        Some(document.to_string())
+    }
+
+    /// whether `text` (in this format) has comments
+    pub fn has_comments(self, text: &str) -> bool {
+        let mut quote = None;
+        let mut escaped = false;
+        let mut previous = '\n';
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quote, self, c) {
+                (Some(_), _, _) if escaped => escaped = false,
+                (Some('"'), _, '\\') => escaped = true,
+                (Some(q), _, c) if c == q => quote = None,
+                (Some(_), _, _) | (None, FileFormat::Json, _) => {}
+                // YAML quotes start a scalar, not within a plain one
+                (None, FileFormat::Yaml, '"' | '\'')
+                    if previous.is_whitespace() || "[{,:-".contains(previous) =>
+                {
+                    quote = Some(c)
+                }
+                (None, FileFormat::Yaml, '#') if previous.is_whitespace() => return true,
+                (None, FileFormat::Toml, '"' | '\'') | (None, FileFormat::Ron, '"') => {
+                    quote = Some(c)
+                }
+                (None, FileFormat::Toml, '#') => return true,
+                (None, FileFormat::Ron, '/') if matches!(chars.peek(), Some('/' | '*')) => {
+                    return true
+                }
+                _ => {}
+            }
+            previous = c;
+        }
+        false
+    }
+}
+
+// replace the integers above `i64::MAX` by expressions, for TOML
+fn large_integers(value: &mut Value) {
+    match value {
+        Value::Number(n) => {
+            if let Some(n) = n.as_u64().filter(|n| *n > i64::MAX as u64) {
+                *value = format!("$({:#x})", n).into();
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(large_integers),
+        Value::Object(map) => map.values_mut().for_each(large_integers),
+        _ => {}
+    }
+}
+
+// update the TOML item `old` to `new`, keeping the decor (comments and
+// whitespace) of the tables, elements and values kept
+fn merge(old: &mut Item, new: Item) {
+    if let (Some(table), Some(new_table)) = (old.as_table_like_mut(), new.as_table_like()) {
+        let removed: Vec<String> = table
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| !new_table.contains_key(key))
+            .collect();
+        for key in removed {
+            table.remove(&key);
+        }
+        for (key, item) in new_table.iter() {
+            match table.get_mut(key) {
+                Some(kept) => merge(kept, item.clone()),
+                None => {
+                    table.insert(key, item.clone());
+                }
+            }
+        }
+        return;
+    }
+    // an array of tables is kept as such, or as an inline array
+    let new = match (&*old, new) {
+        (Item::Value(_), new @ Item::ArrayOfTables(_)) => {
+            new.into_value().map(Item::Value).unwrap_or_default()
+        }
+        (Item::ArrayOfTables(_), new) => new
+            .into_array_of_tables()
+            .map(Item::ArrayOfTables)
+            .unwrap_or_else(|new| new),
+        (_, new) => new,
+    };
+    match (old, new) {
+        (Item::ArrayOfTables(tables), Item::ArrayOfTables(new)) => {
+            let old = tables.iter().cloned().map(Item::Table).collect();
+            let new = new.iter().cloned().map(Item::Table).collect();
+            tables.clear();
+            for item in merge_elements(old, new) {
+                tables.push(item.into_table().unwrap_or_default());
+            }
+        }
+        (
+            Item::Value(toml_edit::Value::Array(values)),
+            Item::Value(toml_edit::Value::Array(new)),
+        ) => {
+            let old = values.iter().cloned().map(Item::Value).collect();
+            let new = new.iter().cloned().map(Item::Value).collect();
+            values.clear();
+            for item in merge_elements(old, new) {
+                if let Ok(value) = item.into_value() {
+                    values.push_formatted(value);
+                }
+            }
+        }
+        (Item::Value(value), Item::Value(new)) if !same(value, &new) => {
+            let decor = value.decor().clone();
+            *value = new;
+            *value.decor_mut() = decor;
+        }
+        (Item::Value(_), Item::Value(_)) => {}
+        (old, new) => *old = new,
+    }
+}
+
+// the elements of an array `new`, merged into the elements of `old` with the
+// same id (components), or else at the same index
+fn merge_elements(old: Vec<Item>, new: Vec<Item>) -> Vec<Item> {
+    let id = |item: &Item| item.get("id").and_then(Item::as_str).map(String::from);
+    let mut old: Vec<Option<Item>> = old.into_iter().map(Some).collect();
+    new.into_iter()
+        .enumerate()
+        .map(|(index, new)| {
+            let kept = match id(&new) {
+                Some(new_id) => old
+                    .iter()
+                    .position(|o| o.as_ref().and_then(id).as_ref() == Some(&new_id)),
+                None => Some(index),
+            };
+            match kept.and_then(|i| old.get_mut(i)).and_then(Option::take) {
+                Some(mut kept) => {
+                    merge(&mut kept, new);
+                    kept
+                }
+                None => new,
+            }
+        })
+        .collect()
+}
+
+// whether the scalars `a` and `b` are equal (ignoring their representation,
+// e.g., a hex integer)
+fn same(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    use toml_edit::Value::*;
+    match (a, b) {
+        (String(a), String(b)) => a.value() == b.value(),
+        (Integer(a), Integer(b)) => a.value() == b.value(),
+        (Float(a), Float(b)) => a.value() == b.value(),
+        (Boolean(a), Boolean(b)) => a.value() == b.value(),
+        _ => false,
+    }
+}
+
+/// write `value` to `path`, in the format given by the extension, returns
+/// the file written, the comments of a TOML file are kept, a RON or YAML
+/// file with comments is not overwritten, `<name>.new.<ext>` is written
+/// instead
+pub fn write_file(path: &Path, value: &Value) -> Result<PathBuf, SimError> {
+    let format = FileFormat::from_path(path);
+    let mut path = path.to_path_buf();
+    let mut text = format.write(value)?;
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if format.has_comments(&existing) {
+            match format.update(&existing, value) {
+                Some(updated) => text = updated,
+                None => {
+                    let extension = path.extension().unwrap_or_default().to_string_lossy();
+                    path = path.with_extension(format!("new.{}", extension));
+                    warn!(
+                        "comments would be lost, the model is written to {}",
+                        path.display()
+                    );
+                }
+            }
+        }
+    }
+    trace!("model: {}", text);
+    fs::write(&path, &text).map_err(|error| SimError::Io {
+        path: path.clone(),
+        error,
+    })?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{ComponentStore, Input, Signal, Simulator};
+    use crate::components::*;
+    use std::{path::PathBuf, rc::Rc};
+
+    fn model() -> ComponentStore {
+        ComponentStore {
+            store: vec![
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 10.5),
+                    value: Signal::MAX,
+                }),
+                Rc::new(Mux {
+                    id: "mux".into(),
+                    pos: (0.0, 0.0),
+                    select: Input::new("c", "out"),
+                    m_in: vec![Input::new("c", "out")],
+                }),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        for ext in EXTENSIONS {
+            let path = std::env::temp_dir().join(format!("syncrim_test_format.{}", ext));
+            model().save_file(&path).unwrap();
+            let loaded = ComponentStore::load_file(&path).unwrap();
+            assert_eq!(
+                serde_json::to_value(&loaded).unwrap(),
+                serde_json::to_value(model()).unwrap(),
+                "{}",
+                ext
+            );
+        }
+        // TOML holds `Signal::MAX` as an expression
+        let path = std::env::temp_dir().join("syncrim_test_format.toml");
+        let toml = std::fs::read_to_string(path).unwrap();
+        assert!(
+            toml.contains(r#"value = "$(0xffffffffffffffff)""#),
+            "{}",
+            toml
+        );
+    }
+
+    #[test]
+    fn test_has_comments() {
+        for (format, text, comments) in [
+            (FileFormat::Json, r##"{"id": "# //"}"##, false),
+            (FileFormat::Yaml, "id: c # the constant", true),
+            (FileFormat::Yaml, "# model\nid: c", true),
+            (FileFormat::Yaml, "id: c#1", false),
+            (FileFormat::Yaml, "id: 'c # 1'", false),
+            (FileFormat::Yaml, "label: it's # quoted", true),
+            (FileFormat::Toml, r#"id = "c # \" 1""#, false),
+            (FileFormat::Toml, "id = 'c' # the constant", true),
+            (FileFormat::Ron, r#"(id: "c // 1")"#, false),
+            (FileFormat::Ron, "(id: \"c\") /* the constant */", true),
+        ] {
+            assert_eq!(format.has_comments(text), comments, "{}", text);
+        }
+
+        // a YAML file with comments is not overwritten
+        let path = std::env::temp_dir().join("syncrim_test_comments.yaml");
+        std::fs::write(&path, "# keep\nstore: []\n").unwrap();
+        assert_eq!(
+            model().save_file(&path).unwrap(),
+            path.with_extension("new.yaml")
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# keep\nstore: []\n"
+        );
+    }
+
+    #[test]
+    fn test_toml_comments() {
+        let path = std::env::temp_dir().join("syncrim_test_comments.toml");
+        let toml = r#"
+            # the model
+            [[store]]
+            type = "Constant" # a constant
+            id = "c"
+            pos = [0.0, 10.5]
+            value = 0x10 # kept as hex
+
+            # the mux
+            [[store]]
+            type = "Mux"
+            id = "mux"
+            pos = [0.0, 0.0]
+            select = { id = "c", field = "out" }
+            m_in = [
+                { id = "c", field = "out" }, # the only input
+            ]
+        "#;
+        std::fs::write(&path, toml.replace("\n            ", "\n")).unwrap();
+        let mut model = model();
+        model.store[0] = Rc::new(Constant {
+            id: "c".into(),
+            pos: (0.0, 10.5),
+            value: 16,
+        });
+        assert_eq!(model.save_file(&path).unwrap(), path);
+        let toml = std::fs::read_to_string(&path).unwrap();
+        for kept in [
+            "# the model",
+            "# a constant",
+            "value = 0x10 # kept as hex",
+            "# the mux",
+            "# the only input",
+        ] {
+            assert!(toml.contains(kept), "{}", toml);
+        }
+
+        // the mux removed, the constant changed
+        model.store = vec![Rc::new(Constant {
+            id: "c".into(),
+            pos: (0.0, 10.5),
+            value: 17,
+        })];
+        model.save_file(&path).unwrap();
+        let toml = std::fs::read_to_string(&path).unwrap();
+        assert!(toml.contains("value = 17 # kept as hex"), "{}", toml);
+        assert!(!toml.contains("mux"), "{}", toml);
+        assert_eq!(
+            serde_json::to_value(ComponentStore::load_file(&path).unwrap()).unwrap(),
+            serde_json::to_value(&model).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let path = PathBuf::from("model.yaml");
+        let yaml = "
+            # parameters of the model
+            parameters: { N: 2 }
+            store:
+              - { type: Constant, id: c, pos: [0, 0], value: $N } # the constant
+        ";
+        let yaml = yaml.replace("\n            ", "\n");
+        let model = FileFormat::from_path(&path).parse(&yaml).unwrap();
+        let cs = ComponentStore::load_model(model, &path).unwrap();
+        let mut clock = 0;
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
+        assert_eq!(simulator.get_input_val(&Input::new("c", "out")), 2);
+
+        let ron = r#"{
+            // a constant
+            "store": [{"type": "Constant", "id": "c", "pos": (0, 0), "value": 3}],
+        }"#;
+        assert_eq!(FileFormat::Ron.parse(ron).unwrap()["store"][0]["value"], 3);
+        assert!(FileFormat::Toml.parse("store = [").is_err());
+    }
+}
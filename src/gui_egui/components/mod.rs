@@ -6,4 +6,5 @@ mod probe;
 mod probe_out;
 mod register;
 mod sext;
+mod sub_circuit;
 mod wire;
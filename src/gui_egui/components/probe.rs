@@ -17,6 +17,7 @@ impl EguiComponent for Probe {
         offset.y += self.pos.1 * scale;
         let input = self.input.clone();
         let value = simulator.get_input_val(&input);
+        let text = simulator.format_input(&input);
         Area::new(self.id.to_string())
             .order(Order::Middle)
             .current_pos(offset.to_pos2())
@@ -26,7 +27,7 @@ impl EguiComponent for Probe {
             .show(ui.ctx(), |ui| {
                 ui.set_clip_rect(clip_rect);
                 ui.label(
-                    RichText::new(format!("{:?}", value))
+                    RichText::new(text)
                         .size(scale * 12f32)
                         .background_color(Color32::LIGHT_BLUE),
                 )
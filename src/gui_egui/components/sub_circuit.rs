@@ -0,0 +1,40 @@
+use crate::common::{EguiComponent, Simulator};
+use crate::components::SubCircuit;
+use egui::{Align2, Color32, FontId, Rect, Stroke, Vec2};
+
+#[typetag::serde]
+impl EguiComponent for SubCircuit {
+    fn render(
+        &self,
+        ui: &mut egui::Ui,
+        _simulator: Simulator,
+        offset: egui::Vec2,
+        scale: f32,
+        _clip_rect: egui::Rect,
+    ) {
+        let mut offset = offset;
+        offset.x += self.pos.0 * scale;
+        offset.y += self.pos.1 * scale;
+
+        // a box labeled by the id, opened from the side panel
+        let rect = Rect::from_center_size(
+            offset.to_pos2(),
+            Vec2::new(self.width * scale, self.height * scale),
+        );
+        ui.painter().rect_stroke(
+            rect,
+            0.0,
+            Stroke {
+                width: scale,
+                color: Color32::BLACK,
+            },
+        );
+        ui.painter().text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            &self.id,
+            FontId::proportional(12.0 * scale),
+            Color32::BLACK,
+        );
+    }
+}
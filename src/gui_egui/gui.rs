@@ -1,13 +1,19 @@
-use crate::common::{ComponentStore, Simulator};
-use crate::gui_egui::{keymap, keymap::Shortcuts, menu::Menu};
+use crate::breakpoint::Breakpoint;
+use crate::common::{ComponentStore, Id, Signal, Simulator};
+use crate::gui_egui::{helper::offset_helper, keymap, keymap::Shortcuts, menu::Menu};
+use crate::lint::{self, Problem, Severity};
+use crate::player::{Player, RATES};
 use eframe::egui;
+use log::*;
+use std::collections::{BTreeSet, HashSet};
 use std::path::PathBuf;
+use std::time::Instant;
 
 pub struct Gui {
     pub simulator: Simulator,
     pub path: PathBuf,
     // History, acts like a stack
-    pub history: Vec<Vec<u32>>,
+    pub history: Vec<Vec<Signal>>,
     pub scale: f32,
     pub clock: usize,
     // When the ui elements change size
@@ -17,14 +23,35 @@ pub struct Gui {
     pub clip_rect: egui::Rect,
     pub shortcuts: Shortcuts,
     pub pause: bool,
+    pub player: Player,
+    // Combinatorial loops found when building the model
+    pub loops: Vec<String>,
+    pub breakpoint_text: String,
+    // Last breakpoint hit, or breakpoint parse error
+    pub breakpoint_status: String,
+    // Problems found in the model
+    pub problems: Vec<Problem>,
+    // Sub-circuits opened, showing their components
+    pub opened: HashSet<Id>,
 }
 
 pub fn gui(cs: &ComponentStore, path: &PathBuf) -> Result<(), eframe::Error> {
+    let problems = lint::check(cs);
     let mut clock = 0;
-    let simulator = Simulator::new(cs, &mut clock);
+    // a model with combinatorial loops is shown with the loops highlighted
+    let simulator = match Simulator::view(cs, &mut clock) {
+        Ok(simulator) => simulator,
+        Err(err) => {
+            error!("Failed to build simulator: {}", err);
+            return Ok(());
+        }
+    };
+    let loops = simulator.loops.iter().map(|l| l.to_string()).collect();
     let options = eframe::NativeOptions::default();
     let path = path.to_owned();
-    simulator.save_dot(&path);
+    if let Err(err) = simulator.save_dot(&path) {
+        warn!("Failed to save dot file: {}", err);
+    }
     let gui = Gui {
         clock,
         path,
@@ -37,6 +64,12 @@ pub fn gui(cs: &ComponentStore, path: &PathBuf) -> Result<(), eframe::Error> {
         clip_rect: egui::Rect::NOTHING,
         shortcuts: Shortcuts::new(),
         pause: true,
+        player: Player::new(RATES[1]),
+        loops,
+        breakpoint_text: String::new(),
+        breakpoint_status: String::new(),
+        problems,
+        opened: HashSet::new(),
     };
     eframe::run_native("SyncRim", options, Box::new(|_cc| Box::new(gui)))
 }
@@ -44,6 +77,16 @@ pub fn gui(cs: &ComponentStore, path: &PathBuf) -> Result<(), eframe::Error> {
 impl eframe::App for Gui {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.shortcuts.inputs(ctx, self);
+        if !self.pause {
+            // continuous clocking, repaint to keep playing
+            if self
+                .player
+                .update(&mut self.simulator, &mut self.clock, Instant::now())
+            {
+                self.check_hits();
+            }
+            ctx.request_repaint();
+        }
         let frame = egui::Frame::none().fill(egui::Color32::WHITE);
 
         // For getting the correct offset for our drawing we need to get the top bar
@@ -70,8 +113,11 @@ impl eframe::App for Gui {
         } else {
             self.top_bar(ctx);
             self.side_panel(ctx);
+            self.breakpoint_panel(ctx);
+            self.problems_panel(ctx);
             self.draw_area(ctx, frame);
         }
+        self.loops_window(ctx);
     }
 }
 
@@ -105,6 +151,12 @@ impl Gui {
             ui.set_clip_rect(self.clip_rect);
             // Don't draw over the rest of the ui
             for c in &self.simulator.ordered_components {
+                let id = c.get_id_ports().0;
+                // shown if the enclosing sub-circuits are opened
+                let enclosing = self.simulator.enclosing(&id);
+                if !enclosing.iter().all(|id| self.opened.contains(id)) {
+                    continue;
+                }
                 c.render(
                     ui,
                     self.simulator.clone(),
@@ -112,6 +164,31 @@ impl Gui {
                     self.scale,
                     self.clip_rect,
                 );
+                // highlight the outputs closing a loop
+                let fields: Vec<&str> = self
+                    .simulator
+                    .loop_fields(&id)
+                    .iter()
+                    .map(|f| f.as_str())
+                    .collect();
+                if let (false, Some(pos)) = (fields.is_empty(), c.pos()) {
+                    let center = offset_helper(pos, self.scale, self.offset + self.pan);
+                    ui.painter().circle_stroke(
+                        center,
+                        30.0 * self.scale,
+                        egui::Stroke {
+                            width: 2.0 * self.scale,
+                            color: egui::Color32::RED,
+                        },
+                    );
+                    ui.painter().text(
+                        center - egui::vec2(0.0, 32.0 * self.scale),
+                        egui::Align2::CENTER_BOTTOM,
+                        format!("loop: {}", fields.join(", ")),
+                        egui::FontId::monospace(12.0 * self.scale),
+                        egui::Color32::RED,
+                    );
+                }
             }
         });
         let cpr = central_panel.response.interact(egui::Sense::drag());
@@ -131,6 +208,18 @@ impl Gui {
 
     fn side_panel(&mut self, ctx: &egui::Context) {
         egui::SidePanel::left("leftGui").show(ctx, |ui| {
+            // open (or collapse) the sub-circuits
+            let sub_circuits: BTreeSet<&Id> = self.simulator.parents.values().collect();
+            for id in sub_circuits {
+                let mut open = self.opened.contains(id);
+                if ui.checkbox(&mut open, id.as_str()).changed() {
+                    if open {
+                        self.opened.insert(id.clone());
+                    } else {
+                        self.opened.remove(id);
+                    }
+                }
+            }
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.horizontal(|ui| {
                     ui.label("0x00000004\n0x00000008\n".repeat(100));
@@ -140,6 +229,100 @@ impl Gui {
         });
     }
 
+    fn breakpoint_panel(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("breakpoints").show(ctx, |ui| {
+            ui.heading("Breakpoints");
+            let response = ui.text_edit_singleline(&mut self.breakpoint_text);
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                let result = self
+                    .breakpoint_text
+                    .parse::<Breakpoint>()
+                    .map_err(|e| e.to_string())
+                    .and_then(|b| self.simulator.add_breakpoint(b).map_err(|e| e.to_string()));
+                match result {
+                    Ok(()) => {
+                        self.breakpoint_text.clear();
+                        self.breakpoint_status.clear();
+                    }
+                    Err(err) => self.breakpoint_status = err,
+                }
+            }
+            ui.weak("e.g., reg.out=5, reg.out>5,\nreg.out&0xf=0x3, reg.out (changes),\nmem@0x10:w");
+            let mut remove = None;
+            for (i, b) in self.simulator.breakpoints.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(b.to_string());
+                    if ui.small_button("x").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                self.simulator.remove_breakpoint(i);
+            }
+            ui.colored_label(egui::Color32::RED, &self.breakpoint_status);
+        });
+    }
+
+    fn problems_panel(&mut self, ctx: &egui::Context) {
+        if self.problems.is_empty() {
+            return;
+        }
+        egui::TopBottomPanel::bottom("problems").show(ctx, |ui| {
+            ui.heading("Problems");
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for problem in &self.problems {
+                    let color = match problem.severity() {
+                        Severity::Error => egui::Color32::RED,
+                        Severity::Warning => egui::Color32::from_rgb(255, 140, 0),
+                    };
+                    ui.colored_label(color, problem.to_string());
+                }
+            });
+        });
+    }
+
+    // replace the simulator, keeping the breakpoints that still apply
+    pub fn set_simulator(&mut self, mut simulator: Simulator, clock: usize) {
+        for breakpoint in self.simulator.breakpoints.drain(..) {
+            if let Err(err) = simulator.add_breakpoint(breakpoint) {
+                warn!("Breakpoint removed: {}", err);
+            }
+        }
+        self.simulator = simulator;
+        self.clock = clock;
+    }
+
+    // pause on breakpoint hits
+    pub fn check_hits(&mut self) {
+        if !self.simulator.hits.is_empty() {
+            self.pause = true;
+            self.breakpoint_status = self
+                .simulator
+                .hits
+                .iter()
+                .map(|h| h.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+    }
+
+    fn loops_window(&mut self, ctx: &egui::Context) {
+        if self.loops.is_empty() {
+            return;
+        }
+        egui::Window::new("Combinatorial loops")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                for l in &self.loops {
+                    ui.colored_label(egui::Color32::RED, l);
+                }
+                if ui.button("Ok").clicked() {
+                    self.loops.clear();
+                }
+            });
+    }
+
     fn top_bar(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::top("topBar").show(ctx, |ui| Menu::new(ui, self));
     }
@@ -194,6 +194,44 @@ pub fn file_new_fn(_gui: &mut crate::gui_egui::gui::Gui) {}
 pub fn file_open_fn(_gui: &mut crate::gui_egui::gui::Gui) {}
 pub fn file_save_fn(_gui: &mut crate::gui_egui::gui::Gui) {}
 pub fn file_save_as_fn(_gui: &mut crate::gui_egui::gui::Gui) {}
+pub fn file_export_vcd_fn(gui: &mut crate::gui_egui::gui::Gui) {
+    let mut path = gui.path.clone();
+    path.set_extension("vcd");
+    let file = rfd::FileDialog::new()
+        .add_filter("vcd", &["vcd"])
+        .set_file_name(&path.file_name().unwrap_or_default().to_string_lossy())
+        .save_file();
+    if let Some(path_buf) = file {
+        if let Err(err) = gui.simulator.save_vcd(&path_buf) {
+            log::error!("Failed to export VCD: {}", err);
+        }
+    }
+}
+pub fn file_save_checkpoint_fn(gui: &mut crate::gui_egui::gui::Gui) {
+    let file = rfd::FileDialog::new()
+        .add_filter("checkpoint", &["json"])
+        .save_file();
+    if let Some(path_buf) = file {
+        if let Err(err) = gui.simulator.save_checkpoint(&path_buf) {
+            log::error!("Failed to save checkpoint: {}", err);
+        }
+    }
+}
+pub fn file_load_checkpoint_fn(gui: &mut crate::gui_egui::gui::Gui) {
+    let file = rfd::FileDialog::new()
+        .add_filter("checkpoint", &["json"])
+        .pick_file();
+    if let Some(path_buf) = file {
+        let mut clock = 0;
+        match crate::common::Simulator::load_checkpoint(&path_buf, &mut clock) {
+            Ok(simulator) => {
+                gui.set_simulator(simulator, clock);
+                gui.ui_change = true;
+            }
+            Err(err) => log::error!("Failed to load checkpoint {:?}: {}", path_buf, err),
+        }
+    }
+}
 pub fn file_preferences_fn(_gui: &mut crate::gui_egui::gui::Gui) {}
 pub fn file_quit_fn(_gui: &mut crate::gui_egui::gui::Gui) {}
 pub fn edit_cut_fn(_gui: &mut crate::gui_egui::gui::Gui) {}
@@ -220,10 +258,15 @@ pub fn view_zoom_out_fn(gui: &mut crate::gui_egui::gui::Gui) {
     }
 }
 pub fn control_play_toggle(gui: &mut crate::gui_egui::gui::Gui) {
-    gui.pause = !gui.pause;
+    if gui.pause {
+        control_play(gui);
+    } else {
+        control_pause(gui);
+    }
 }
 pub fn control_play(gui: &mut crate::gui_egui::gui::Gui) {
     gui.pause = false;
+    gui.player.start(std::time::Instant::now());
 }
 pub fn control_pause(gui: &mut crate::gui_egui::gui::Gui) {
     gui.pause = true;
@@ -232,8 +275,13 @@ pub fn control_reset(gui: &mut crate::gui_egui::gui::Gui) {
     gui.simulator.reset(&mut gui.clock);
     gui.pause = true;
 }
+pub fn control_four_state(gui: &mut crate::gui_egui::gui::Gui, four_state: bool) {
+    gui.simulator.set_four_state(four_state, &mut gui.clock);
+    gui.pause = true;
+}
 pub fn control_step_forward(gui: &mut crate::gui_egui::gui::Gui) {
     gui.simulator.clock(&mut gui.clock);
+    gui.check_hits();
 }
 pub fn control_step_back(gui: &mut crate::gui_egui::gui::Gui) {
     gui.simulator.un_clock(&mut gui.clock);
@@ -1,3 +1,6 @@
+use crate::player::RATES;
+use std::time::Instant;
+
 pub(crate) struct Menu {}
 
 impl Menu {
@@ -28,6 +31,15 @@ impl Menu {
                 if btn(ui, "Save As", gui.shortcuts.file_save_as).clicked() {
                     // Save As here
                 }
+                if ui.button("Export VCD").clicked() {
+                    crate::gui_egui::keymap::file_export_vcd_fn(gui);
+                }
+                if ui.button("Save Checkpoint").clicked() {
+                    crate::gui_egui::keymap::file_save_checkpoint_fn(gui);
+                }
+                if ui.button("Load Checkpoint").clicked() {
+                    crate::gui_egui::keymap::file_load_checkpoint_fn(gui);
+                }
                 ui.separator();
                 if btn(ui, "Preferences", gui.shortcuts.file_preferences).clicked() {
                     // Preferences here
@@ -109,6 +121,25 @@ impl Menu {
             if ui.button("⏸").clicked() {
                 crate::gui_egui::keymap::control_pause(gui);
             }
+            egui::ComboBox::from_id_source("rate")
+                .selected_text(gui.player.rate.to_string())
+                .show_ui(ui, |ui| {
+                    for rate in RATES {
+                        if ui
+                            .selectable_label(gui.player.rate == rate, rate.to_string())
+                            .clicked()
+                        {
+                            gui.player.set_rate(rate, Instant::now());
+                        }
+                    }
+                });
+            if !gui.pause {
+                ui.label(format!("{:.0} Hz", gui.player.achieved));
+            }
+            let mut four_state = gui.simulator.four_state;
+            if ui.checkbox(&mut four_state, "Four-state").changed() {
+                crate::gui_egui::keymap::control_four_state(gui, four_state);
+            }
             ui.label(format!("Clock #{}", gui.clock));
         });
     }
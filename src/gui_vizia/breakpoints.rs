@@ -0,0 +1,57 @@
+use crate::gui_vizia::GuiData;
+use vizia::{icons, prelude::*};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakpointEvent {
+    Add(String),
+    Remove(usize),
+}
+
+// Breakpoint panel, listing the breakpoints and the last hit
+pub(crate) struct Breakpoints {}
+
+impl View for Breakpoints {}
+
+impl Breakpoints {
+    pub(crate) fn new(cx: &mut Context) -> Handle<Self> {
+        View::build(Breakpoints {}, cx, |cx| {
+            VStack::new(cx, |cx| {
+                Label::new(cx, "Breakpoints").class("title");
+                Textbox::new(cx, GuiData::breakpoint_text)
+                    .on_submit(|ex, text, enter| {
+                        if enter {
+                            ex.emit(BreakpointEvent::Add(text));
+                        }
+                    })
+                    .width(Pixels(200.0));
+                for hint in [
+                    "e.g., reg.out=5, reg.out>5,",
+                    "reg.out&0xf=0x3, reg.out (changes),",
+                    "mem@0x10:w",
+                ] {
+                    Label::new(cx, hint).class("tt_shortcut");
+                }
+                Binding::new(cx, GuiData::breakpoints, |cx, breakpoints| {
+                    for (i, b) in breakpoints.get(cx).iter().enumerate() {
+                        HStack::new(cx, |cx| {
+                            Label::new(cx, b)
+                                .top(Stretch(1.0))
+                                .bottom(Stretch(1.0))
+                                .width(Stretch(1.0));
+                            Button::new(
+                                cx,
+                                move |ex| ex.emit(BreakpointEvent::Remove(i)),
+                                |cx| Label::new(cx, icons::ICON_X).class("icon"),
+                            );
+                        })
+                        .height(Auto);
+                    }
+                });
+                Label::new(cx, GuiData::breakpoint_status).class("hit");
+            })
+            .row_between(Pixels(5.0))
+            .child_space(Pixels(5.0))
+            .width(Pixels(220.0));
+        })
+    }
+}
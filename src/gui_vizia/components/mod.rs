@@ -7,4 +7,5 @@ mod probe_edit;
 mod probe_out;
 mod register;
 mod sext;
+mod sub_circuit;
 mod wire;
@@ -20,11 +20,15 @@ impl ViziaComponent for Probe {
             let input = self.input.clone();
 
             Binding::new(cx, crate::gui_vizia::GuiData::clock, move |cx, _| {
-                Label::new(cx, {
-                    let simulator = GuiData::simulator.get(cx);
-                    &format!(" {:?}", simulator.get_input_val(&input))
-                })
-                .hoverable(false);
+                let input = input.clone();
+                // updated on settle as well
+                Binding::new(cx, GuiData::settled, move |cx, _| {
+                    Label::new(cx, {
+                        let simulator = GuiData::simulator.get(cx);
+                        &format!(" {}", simulator.format_input(&input))
+                    })
+                    .hoverable(false);
+                });
             });
             NewPopup::new(cx, self.get_id_ports()).position_type(PositionType::SelfDirected);
         })
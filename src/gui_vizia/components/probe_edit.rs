@@ -1,9 +1,8 @@
-use crate::gui_vizia::GuiData;
+use crate::gui_vizia::{GuiData, GuiEvent};
 use crate::{
-    common::{Signal, ViziaComponent},
+    common::{parse_signal, ViziaComponent},
     components::{ProbeEdit, TextSignal},
 };
-use anyhow::{anyhow, Result};
 use vizia::prelude::*;
 
 use log::*;
@@ -31,6 +30,7 @@ impl ViziaComponent for ProbeEdit {
             .on_submit(move |ex, text, enter| {
                 trace!("submit: text {} enter {}", text, enter);
                 ex.emit(ProbeEditViewSetter::EditableText(text));
+                ex.emit(GuiEvent::Edited);
             })
             .on_edit(move |_ex, text| {
                 trace!("edit: text {}", text);
@@ -57,16 +57,3 @@ impl ViziaComponent for ProbeEdit {
 pub struct ProbeEditView {
     editable_text: String,
 }
-
-fn parse_signal(text: &str) -> Result<Signal, anyhow::Error> {
-    let text = text.trim();
-
-    if let Ok(signal) = text.parse::<Signal>() {
-        Ok(signal)
-    } else if let Some(hex) = text.strip_prefix("0x") {
-        let signal = Signal::from_str_radix(hex, 16)?;
-        Ok(signal)
-    } else {
-        Err(anyhow!("Failed to parse {}", text))
-    }
-}
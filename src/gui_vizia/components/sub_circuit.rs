@@ -0,0 +1,81 @@
+use crate::{
+    common::{Component, ViziaComponent},
+    components::SubCircuit,
+    gui_vizia::{tooltip::new_component_tooltip, GuiData, SubCircuitEvent},
+};
+
+use vizia::{
+    icons,
+    prelude::*,
+    vg::{Paint, Path},
+};
+
+use log::*;
+
+#[typetag::serde]
+impl ViziaComponent for SubCircuit {
+    // create view, pressing opens (or collapses) the sub-circuit
+    fn view(&self, cx: &mut Context) {
+        trace!("---- Create SubCircuit View");
+        let id = self.id.clone();
+        View::build(SubCircuitView {}, cx, |cx| {
+            HStack::new(cx, |cx| {
+                let id = self.id.clone();
+                Label::new(
+                    cx,
+                    GuiData::opened.map(move |opened| {
+                        if opened.contains(&id) {
+                            icons::ICON_CHEVRON_DOWN
+                        } else {
+                            icons::ICON_CHEVRON_RIGHT
+                        }
+                    }),
+                )
+                .class("icon")
+                .hoverable(false);
+                Label::new(cx, &self.id).hoverable(false);
+            })
+            .left(Pixels(5.0))
+            .top(Pixels(5.0))
+            .size(Auto);
+        })
+        .position_type(PositionType::SelfDirected)
+        .left(Pixels(self.pos.0 - self.width / 2.0))
+        .top(Pixels(self.pos.1 - self.height / 2.0))
+        .width(Pixels(self.width))
+        .height(Pixels(self.height))
+        .on_press(move |ex| ex.emit(SubCircuitEvent::Toggle(id.clone())))
+        .tooltip(|cx| new_component_tooltip(cx, self));
+    }
+}
+
+struct SubCircuitView {}
+
+impl View for SubCircuitView {
+    fn element(&self) -> Option<&'static str> {
+        Some("SubCircuit")
+    }
+
+    fn draw(&self, cx: &mut DrawContext<'_>, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+
+        let mut path = Path::new();
+        let mut paint = Paint::color(vizia::vg::Color::rgbf(0.0, 0.0, 0.0));
+        paint.set_line_width(cx.logical_to_physical(1.0));
+
+        let left = bounds.left();
+        let right = bounds.right();
+        let top = bounds.top();
+        let bottom = bounds.bottom();
+
+        // draw box, outlined only as the components are shown inside when
+        // opened
+        path.move_to(left + 0.5, top + 0.5);
+        path.line_to(right + 0.5, top + 0.5);
+        path.line_to(right + 0.5, bottom + 0.5);
+        path.line_to(left + 0.5, bottom + 0.5);
+        path.line_to(left + 0.5, top + 0.5);
+
+        canvas.stroke_path(&path, &paint);
+    }
+}
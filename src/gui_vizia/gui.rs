@@ -1,8 +1,24 @@
-use crate::common::{ComponentStore, Simulator};
-use crate::gui_vizia::{grid::Grid, keymap::init_keymap, menu::Menu, transport::Transport};
+use crate::breakpoint::Breakpoint;
+use crate::common::{ComponentStore, Id, Simulator};
+use crate::format;
+use crate::gui_vizia::{
+    breakpoints::{BreakpointEvent, Breakpoints},
+    grid::Grid,
+    keymap::init_keymap,
+    menu::Menu,
+    problems::Problems,
+    transport::Transport,
+};
+use crate::lint;
+use crate::player::{Player, RATES};
 use rfd::FileDialog;
 use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
 use vizia::{icons, prelude::*};
 
 use log::*;
@@ -13,26 +29,65 @@ pub struct GuiData {
     pub clock: usize,
     pub simulator: Simulator,
     pub pause: bool,
+    pub player: Player,
+    pub rates: Vec<String>,
+    pub rate_index: usize,
+    // Achieved clocks per second when playing
+    pub achieved: f64,
+    // Generation of the ticker, the thread emitting `Tick` while playing
+    // runs as long as its generation is current
+    pub ticker: Arc<AtomicUsize>,
+    // Settle the current clock when a value is edited
+    pub settle_on_edit: bool,
+    // Number of settles, for updating views of the current clock
+    pub settled: usize,
     pub is_saved: bool,
     pub show_about: bool,
+    pub show_loops: bool,
+    pub loops: Vec<String>,
+    pub breakpoint_text: String,
+    pub breakpoints: Vec<String>,
+    // Last breakpoint hit, or breakpoint parse error
+    pub breakpoint_status: String,
+    // Problems found in the model
+    pub problems: Vec<String>,
     pub selected_id: usize,
     pub visible: HashSet<usize>,
     pub expanded: HashSet<usize>,
+    // Sub-circuits opened, showing their components
+    pub opened: HashSet<Id>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubCircuitEvent {
+    // open or collapse sub-circuit `id`
+    Toggle(Id),
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum GuiEvent {
     Open,
     ReOpen,
+    ExportVcd,
+    SaveCheckpoint,
+    LoadCheckpoint,
     Clock,
     Reset,
     UnClock,
     Play,
     Pause,
     PlayToggle,
+    SetRate(usize),
+    // Periodic update, clocking the simulator when playing
+    Tick,
+    // A value was edited (e.g., a `ProbeEdit`)
+    Edited,
+    ToggleSettle,
+    ToggleFourState,
     Preferences,
     ShowAbout,
     HideAbout,
+    HideLoops,
     ShowLeftPanel(usize),
     HideLeftPanel(usize),
     ToggleExpandLeftPanel(usize),
@@ -40,7 +95,7 @@ pub enum GuiEvent {
 }
 
 impl Model for GuiData {
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         #[allow(clippy::single_match)]
         event.map(|window_event, meta| match window_event {
             // Intercept WindowClose event to show a dialog if not 'saved'.
@@ -56,7 +111,9 @@ impl Model for GuiData {
 
         event.map(|app_event, _meta| match app_event {
             GuiEvent::Open => {
-                let files = FileDialog::new().add_filter("json", &["json"]).pick_file();
+                let files = FileDialog::new()
+                    .add_filter("model", &format::EXTENSIONS)
+                    .pick_file();
                 trace!("files {:?}", files);
                 if let Some(path_buf) = files {
                     self.path = path_buf;
@@ -64,18 +121,100 @@ impl Model for GuiData {
                 }
             }
             GuiEvent::ReOpen => self.open(),
-            GuiEvent::Clock => self.simulator.clock(&mut self.clock),
+            GuiEvent::ExportVcd => {
+                let mut path = self.path.clone();
+                path.set_extension("vcd");
+                let file = FileDialog::new()
+                    .add_filter("vcd", &["vcd"])
+                    .set_file_name(&path.file_name().unwrap_or_default().to_string_lossy())
+                    .save_file();
+                trace!("file {:?}", file);
+                if let Some(path_buf) = file {
+                    if let Err(err) = self.simulator.save_vcd(&path_buf) {
+                        error!("Failed to export VCD: {}", err);
+                    }
+                }
+            }
+            GuiEvent::SaveCheckpoint => {
+                let file = FileDialog::new()
+                    .add_filter("checkpoint", &["json"])
+                    .save_file();
+                trace!("file {:?}", file);
+                if let Some(path_buf) = file {
+                    if let Err(err) = self.simulator.save_checkpoint(&path_buf) {
+                        error!("Failed to save checkpoint: {}", err);
+                    }
+                }
+            }
+            GuiEvent::LoadCheckpoint => {
+                let file = FileDialog::new()
+                    .add_filter("checkpoint", &["json"])
+                    .pick_file();
+                trace!("file {:?}", file);
+                if let Some(path_buf) = file {
+                    let mut clock = 0;
+                    match Simulator::load_checkpoint(&path_buf, &mut clock) {
+                        Ok(simulator) => {
+                            self.set_simulator(simulator, clock);
+                            self.pause = true;
+                        }
+                        Err(err) => error!("Failed to load checkpoint {:?}: {}", path_buf, err),
+                    }
+                }
+            }
+            GuiEvent::Clock => {
+                self.simulator.clock(&mut self.clock);
+                self.check_hits();
+            }
             GuiEvent::UnClock => self.simulator.un_clock(&mut self.clock),
             GuiEvent::Reset => {
                 self.simulator.reset(&mut self.clock);
                 self.pause = true;
             }
-            GuiEvent::Play => self.pause = false,
+            GuiEvent::Play => self.play(cx),
             GuiEvent::Pause => self.pause = true,
-            GuiEvent::PlayToggle => self.pause = !self.pause,
+            GuiEvent::PlayToggle => {
+                if self.pause {
+                    self.play(cx);
+                } else {
+                    self.pause = true;
+                }
+            }
+            GuiEvent::SetRate(index) => {
+                self.rate_index = *index;
+                self.player.set_rate(RATES[*index], Instant::now());
+            }
+            GuiEvent::Tick => {
+                if self.pause {
+                    // stop the ticker
+                    self.ticker.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    let hit =
+                        self.player
+                            .update(&mut self.simulator, &mut self.clock, Instant::now());
+                    self.achieved = self.player.achieved;
+                    if hit {
+                        self.check_hits();
+                    }
+                }
+            }
+            GuiEvent::Edited => {
+                if self.settle_on_edit {
+                    self.simulator.settle();
+                    self.settled += 1;
+                    self.check_hits();
+                }
+            }
+            GuiEvent::ToggleSettle => self.settle_on_edit = !self.settle_on_edit,
+            GuiEvent::ToggleFourState => {
+                let four_state = !self.simulator.four_state;
+                self.simulator.set_four_state(four_state, &mut self.clock);
+                self.pause = true;
+            }
             GuiEvent::Preferences => trace!("Preferences"),
             GuiEvent::ShowAbout => self.show_about = true,
             GuiEvent::HideAbout => self.show_about = false,
+            GuiEvent::HideLoops => self.show_loops = false,
             GuiEvent::ShowLeftPanel(i) => {
                 error!("Show Left Panel {:?}", i);
                 self.visible.insert(*i);
@@ -95,27 +234,137 @@ impl Model for GuiData {
                 }
             }
         });
+
+        event.map(|breakpoint_event, _meta| match breakpoint_event {
+            BreakpointEvent::Add(text) => {
+                let result = text
+                    .parse::<Breakpoint>()
+                    .map_err(|e| e.to_string())
+                    .and_then(|b| self.simulator.add_breakpoint(b).map_err(|e| e.to_string()));
+                match result {
+                    Ok(()) => {
+                        self.breakpoint_text.clear();
+                        self.breakpoint_status.clear();
+                    }
+                    Err(err) => {
+                        self.breakpoint_text = text.clone();
+                        self.breakpoint_status = err;
+                    }
+                }
+                self.update_breakpoints();
+            }
+            BreakpointEvent::Remove(i) => {
+                self.simulator.remove_breakpoint(*i);
+                self.update_breakpoints();
+            }
+        });
+
+        event.map(|sub_circuit_event, _meta| match sub_circuit_event {
+            SubCircuitEvent::Toggle(id) => {
+                if !self.opened.remove(id) {
+                    self.opened.insert(id.clone());
+                }
+            }
+        });
     }
 }
 
 impl GuiData {
-    fn open(&mut self) {
-        // Re-Open model
-        trace!("open path {:?}", self.path);
-        let cs = Box::new(ComponentStore::load_file(&self.path));
-        let simulator = Simulator::new(&cs, &mut self.clock);
+    fn play(&mut self, cx: &mut EventContext) {
+        self.pause = false;
+        self.player.start(Instant::now());
+        self.achieved = 0.0;
+        // periodic updates while playing, replacing any previous ticker
+        let ticker = self.ticker.clone();
+        let generation = ticker.fetch_add(1, Ordering::Relaxed) + 1;
+        cx.spawn(move |cx| {
+            while ticker.load(Ordering::Relaxed) == generation && cx.emit(GuiEvent::Tick).is_ok() {
+                std::thread::sleep(Duration::from_millis(16));
+            }
+        });
+    }
 
+    // replace the simulator, keeping the breakpoints that still apply
+    fn set_simulator(&mut self, mut simulator: Simulator, clock: usize) {
+        for breakpoint in self.simulator.breakpoints.drain(..) {
+            if let Err(err) = simulator.add_breakpoint(breakpoint) {
+                warn!("Breakpoint removed: {}", err);
+            }
+        }
         self.simulator = simulator;
+        self.clock = clock;
+        self.update_breakpoints();
+        self.loops = loops(&self.simulator);
+        self.show_loops = !self.loops.is_empty();
+    }
 
-        trace!("opened");
+    fn update_breakpoints(&mut self) {
+        self.breakpoints = self
+            .simulator
+            .breakpoints
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+    }
+
+    // pause on breakpoint hits
+    fn check_hits(&mut self) {
+        if !self.simulator.hits.is_empty() {
+            self.pause = true;
+            self.breakpoint_status = self
+                .simulator
+                .hits
+                .iter()
+                .map(|h| h.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
     }
+
+    fn open(&mut self) {
+        // Re-Open model, on failure the current model is kept
+        trace!("open path {:?}", self.path);
+        let mut clock = 0;
+        let result = ComponentStore::load_file(&self.path).and_then(|cs| {
+            self.problems = problems(&cs);
+            Simulator::view(&cs, &mut clock)
+        });
+        match result {
+            Ok(simulator) => {
+                self.set_simulator(simulator, clock);
+                trace!("opened");
+            }
+            Err(err) => error!("Failed to open {:?}: {}", self.path, err),
+        }
+    }
+}
+
+// the problems found in the model, as text
+fn problems(cs: &ComponentStore) -> Vec<String> {
+    lint::check(cs).iter().map(|p| p.to_string()).collect()
+}
+
+// the combinatorial loops of the model, as text
+fn loops(simulator: &Simulator) -> Vec<String> {
+    simulator.loops.iter().map(|l| l.to_string()).collect()
 }
 
 pub fn gui(cs: &ComponentStore, path: &PathBuf) {
+    let problems = problems(cs);
     let mut clock = 0;
-    let simulator = Simulator::new(cs, &mut clock);
+    // a model with combinatorial loops is shown with the loops highlighted
+    let simulator = match Simulator::view(cs, &mut clock) {
+        Ok(simulator) => simulator,
+        Err(err) => {
+            error!("Failed to build simulator: {}", err);
+            return;
+        }
+    };
+    let loops = loops(&simulator);
     let path = path.to_owned();
-    simulator.save_dot(&path);
+    if let Err(err) = simulator.save_dot(&path) {
+        warn!("Failed to save dot file: {}", err);
+    }
 
     Application::new(move |cx| {
         cx.add_stylesheet(include_style!("src/gui_vizia/style.css"))
@@ -129,11 +378,25 @@ pub fn gui(cs: &ComponentStore, path: &PathBuf) {
             clock,
             simulator,
             pause: true,
+            player: Player::new(RATES[1]),
+            rates: RATES.iter().map(|r| r.to_string()).collect(),
+            rate_index: 1,
+            achieved: 0.0,
+            ticker: Arc::new(AtomicUsize::new(0)),
+            settle_on_edit: false,
+            settled: 0,
             is_saved: false,
             show_about: false,
+            show_loops: !loops.is_empty(),
+            loops,
+            breakpoint_text: String::new(),
+            breakpoints: vec![],
+            breakpoint_status: String::new(),
+            problems,
             selected_id: 0,
             visible: HashSet::new(),
             expanded: HashSet::new(),
+            opened: HashSet::new(),
         }
         .build(cx);
 
@@ -255,13 +518,42 @@ pub fn gui(cs: &ComponentStore, path: &PathBuf) {
                             |cx, wrapper_oc| {
                                 VStack::new(cx, |cx| {
                                     let oc = wrapper_oc.get(cx);
+                                    let simulator = GuiData::simulator.get(cx);
                                     for (i, c) in oc.iter().enumerate() {
                                         error!("comp id {}", i);
+                                        let id = c.get_id_ports().0;
+                                        // shown if the enclosing sub-circuits are opened
+                                        let enclosing = simulator.enclosing(&id);
+                                        let fields: Vec<&str> = simulator
+                                            .loop_fields(&id)
+                                            .iter()
+                                            .map(|f| f.as_str())
+                                            .collect();
                                         VStack::new(cx, |cx| {
                                             c.view(cx);
+                                            // highlight the outputs closing a loop
+                                            if let (false, Some(pos)) = (fields.is_empty(), c.pos())
+                                            {
+                                                Label::new(
+                                                    cx,
+                                                    &format!("loop: {}", fields.join(", ")),
+                                                )
+                                                .class("loop_member")
+                                                .position_type(PositionType::SelfDirected)
+                                                .left(Pixels(pos.0))
+                                                .top(Pixels(pos.1))
+                                                .hoverable(false);
+                                            }
                                         })
                                         .position_type(PositionType::SelfDirected)
                                         .size(Auto)
+                                        .display(GuiData::opened.map(move |opened| {
+                                            if enclosing.iter().all(|id| opened.contains(id)) {
+                                                Display::Flex
+                                            } else {
+                                                Display::None
+                                            }
+                                        }))
                                         .on_mouse_down(
                                             move |ex, button| {
                                                 if button == MouseButton::Right {
@@ -285,7 +577,11 @@ pub fn gui(cs: &ComponentStore, path: &PathBuf) {
                 .class("bg-default");
 
                 // Right pane
-                Label::new(cx, "Right").top(Pixels(0.0));
+                VStack::new(cx, |cx| {
+                    Breakpoints::new(cx);
+                    Problems::new(cx);
+                })
+                .width(Auto);
             });
 
             //
@@ -310,6 +606,25 @@ pub fn gui(cs: &ComponentStore, path: &PathBuf) {
             })
             .on_blur(|cx| cx.emit(GuiEvent::HideAbout))
             .class("modal");
+
+            // Combinatorial loops
+            Popup::new(cx, GuiData::show_loops, true, |cx| {
+                Label::new(cx, "Combinatorial loops").class("title");
+                Binding::new(cx, GuiData::loops, |cx, loops| {
+                    for l in loops.get(cx) {
+                        Label::new(cx, &l).class("loop");
+                    }
+                });
+
+                Button::new(
+                    cx,
+                    |cx| cx.emit(GuiEvent::HideLoops),
+                    |cx| Label::new(cx, "Ok"),
+                )
+                .class("accent");
+            })
+            .on_blur(|cx| cx.emit(GuiEvent::HideLoops))
+            .class("modal");
         });
     })
     .title("SyncRim")
@@ -99,6 +99,30 @@ impl Menu {
                                 |_| trace!("Save As"),
                                 |cx| Label::new(cx, "Save As"),
                             );
+                            MenuButton::new(
+                                cx,
+                                |cx| {
+                                    trace!("Export VCD");
+                                    cx.emit(GuiEvent::ExportVcd);
+                                },
+                                |cx| Label::new(cx, "Export VCD"),
+                            );
+                            MenuButton::new(
+                                cx,
+                                |cx| {
+                                    trace!("Save Checkpoint");
+                                    cx.emit(GuiEvent::SaveCheckpoint);
+                                },
+                                |cx| Label::new(cx, "Save Checkpoint"),
+                            );
+                            MenuButton::new(
+                                cx,
+                                |cx| {
+                                    trace!("Load Checkpoint");
+                                    cx.emit(GuiEvent::LoadCheckpoint);
+                                },
+                                |cx| Label::new(cx, "Load Checkpoint"),
+                            );
                             MenuDivider::new(cx);
                             MenuButton::new(
                                 cx,
@@ -1,12 +1,14 @@
+mod breakpoints;
+#[cfg(feature = "components")]
+pub mod components;
 mod grid;
 mod gui;
 pub mod hover;
 mod keymap;
 mod menu;
 pub mod popup;
+mod problems;
 pub mod tooltip;
 mod transport;
-#[cfg(feature = "components")]
-pub mod components;
 
 pub use gui::*;
@@ -0,0 +1,31 @@
+use crate::gui_vizia::GuiData;
+use vizia::prelude::*;
+
+// Problems panel, listing the problems found in the model (see `lint`)
+pub(crate) struct Problems {}
+
+impl View for Problems {}
+
+impl Problems {
+    pub(crate) fn new(cx: &mut Context) -> Handle<Self> {
+        View::build(Problems {}, cx, |cx| {
+            VStack::new(cx, |cx| {
+                Label::new(cx, "Problems").class("title");
+                Binding::new(cx, GuiData::problems, |cx, problems| {
+                    let problems = problems.get(cx);
+                    if problems.is_empty() {
+                        Label::new(cx, "No problems found");
+                    }
+                    for problem in problems {
+                        Label::new(cx, &problem)
+                            .class("problem")
+                            .width(Stretch(1.0));
+                    }
+                });
+            })
+            .row_between(Pixels(5.0))
+            .child_space(Pixels(5.0))
+            .width(Pixels(220.0));
+        })
+    }
+}
@@ -1,14 +1,25 @@
-use crate::{common::Component, gui_vizia::GuiData};
+use crate::{
+    common::{Component, Width},
+    gui_vizia::GuiData,
+};
 use vizia::prelude::*;
 
+// port name, with its width if known
+fn port_label(name: &str, width: Width) -> String {
+    match width {
+        Some(width) => format!("{} [{}]", name, width),
+        None => name.to_string(),
+    }
+}
+
 pub fn new_component_tooltip(cx: &mut Context, component: &dyn Component) {
     VStack::new(cx, |cx| {
         let (id, ports) = component.get_id_ports();
         Label::new(cx, &id);
 
-        for input in ports.inputs {
+        for (input, width) in ports.inputs.into_iter().zip(ports.input_widths) {
             HStack::new(cx, |cx| {
-                Label::new(cx, &input.id);
+                Label::new(cx, &port_label(&input.id, width));
                 Binding::new(cx, GuiData::clock, move |cx, _| {
                     Label::new(
                         cx,
@@ -21,8 +32,9 @@ pub fn new_component_tooltip(cx: &mut Context, component: &dyn Component) {
         }
         for output in 0..ports.outputs.len() {
             let id_clone = id.clone();
+            let label = port_label(&format!("out {}", output), ports.output_widths[output]);
             HStack::new(cx, move |cx| {
-                Label::new(cx, &format!("out {}", output));
+                Label::new(cx, &label);
                 Binding::new(cx, GuiData::clock, move |cx, _| {
                     Label::new(
                         cx,
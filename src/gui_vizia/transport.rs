@@ -1,3 +1,4 @@
+use crate::common::Simulator;
 use crate::gui_vizia::{GuiData, GuiEvent};
 use vizia::{icons, prelude::*};
 
@@ -102,6 +103,42 @@ impl Transport {
                     })
                     .size(Auto);
                 });
+
+                // Play rate
+                PickList::new(cx, GuiData::rates, GuiData::rate_index, true)
+                    .on_select(|ex, index| ex.emit(GuiEvent::SetRate(index)))
+                    .width(Pixels(80.0));
+
+                // Achieved clocks per second
+                Label::new(
+                    cx,
+                    GuiData::achieved.map(|achieved| format!("{:.0} Hz", achieved)),
+                )
+                .top(Stretch(1.0))
+                .bottom(Stretch(1.0))
+                .width(Pixels(80.0));
+
+                // Settle the current clock on edits
+                Checkbox::new(cx, GuiData::settle_on_edit)
+                    .on_toggle(|cx| cx.emit(GuiEvent::ToggleSettle))
+                    .top(Stretch(1.0))
+                    .bottom(Stretch(1.0))
+                    .id("settle_on_edit");
+                Label::new(cx, "Settle on edit")
+                    .describing("settle_on_edit")
+                    .top(Stretch(1.0))
+                    .bottom(Stretch(1.0));
+
+                // Four-state (X/Z) signals, resetting the simulation
+                Checkbox::new(cx, GuiData::simulator.then(Simulator::four_state))
+                    .on_toggle(|cx| cx.emit(GuiEvent::ToggleFourState))
+                    .top(Stretch(1.0))
+                    .bottom(Stretch(1.0))
+                    .id("four_state");
+                Label::new(cx, "Four-state")
+                    .describing("four_state")
+                    .top(Stretch(1.0))
+                    .bottom(Stretch(1.0));
             })
             .col_between(Pixels(5.0))
             .size(Auto);
@@ -0,0 +1,245 @@
+// Simulation history, storing the simulation state before each clock.
+//
+// To keep memory bounded for long runs, the history holds a full snapshot
+// every `interval` entries, and in between only the (index, value) pairs
+// that changed since the previous entry. Optionally only the latest
+// `max_depth` entries are kept, older entries are dropped.
+use crate::common::Signal;
+use std::collections::VecDeque;
+
+pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct History {
+    interval: usize,
+    max_depth: Option<usize>,
+    entries: VecDeque<Entry>,
+    // number of entries dropped from the front
+    dropped: usize,
+    // the state of the last entry
+    last: Vec<Signal>,
+}
+
+#[derive(Clone, Debug)]
+enum Entry {
+    Full(Vec<Signal>),
+    Delta(Vec<(usize, Signal)>),
+}
+
+fn apply(state: &mut [Signal], delta: &[(usize, Signal)]) {
+    for (index, value) in delta {
+        state[*index] = *value;
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_SNAPSHOT_INTERVAL, None)
+    }
+}
+
+impl History {
+    pub fn new(interval: usize, max_depth: Option<usize>) -> Self {
+        History {
+            interval: interval.max(1),
+            max_depth,
+            entries: VecDeque::new(),
+            dropped: 0,
+            last: vec![],
+        }
+    }
+
+    /// number of entries pushed, including dropped entries
+    pub fn len(&self) -> usize {
+        self.dropped + self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// number of entries kept
+    pub fn depth(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// number of entries dropped from the front
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// limit the number of entries kept, dropping the oldest entries
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+        self.truncate();
+    }
+
+    /// remove all entries, keeping the configuration
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.dropped = 0;
+        self.last.clear();
+    }
+
+    /// remove all entries, continuing from entry `len` (none of the
+    /// previous entries are kept)
+    pub fn restart(&mut self, len: usize) {
+        self.clear();
+        self.dropped = len;
+    }
+
+    pub fn push(&mut self, state: &[Signal]) {
+        let entry = if self.entries.is_empty() || self.len().is_multiple_of(self.interval) {
+            Entry::Full(state.to_vec())
+        } else {
+            Entry::Delta(
+                state
+                    .iter()
+                    .zip(&self.last)
+                    .enumerate()
+                    .filter(|(_, (new, old))| new != old)
+                    .map(|(index, (new, _))| (index, *new))
+                    .collect(),
+            )
+        };
+        self.entries.push_back(entry);
+        self.last.clear();
+        self.last.extend_from_slice(state);
+        self.truncate();
+    }
+
+    /// remove the last entry, returning its state
+    pub fn pop(&mut self) -> Option<Vec<Signal>> {
+        self.entries.pop_back()?;
+        let state = std::mem::take(&mut self.last);
+        if !self.entries.is_empty() {
+            self.last = self.get(self.len() - 1).unwrap();
+        }
+        Some(state)
+    }
+
+    /// the state of entry `index` (counting dropped entries), if kept
+    pub fn get(&self, index: usize) -> Option<Vec<Signal>> {
+        let pos = index.checked_sub(self.dropped)?;
+        if pos >= self.entries.len() {
+            return None;
+        }
+        // the front entry is always a full snapshot
+        let full = (0..=pos)
+            .rev()
+            .find(|p| matches!(self.entries[*p], Entry::Full(_)))
+            .unwrap();
+        let mut state = vec![];
+        for entry in self.entries.range(full..=pos) {
+            match entry {
+                Entry::Full(s) => state = s.clone(),
+                Entry::Delta(delta) => apply(&mut state, delta),
+            }
+        }
+        Some(state)
+    }
+
+    /// iterate over the states of the kept entries, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = Vec<Signal>> + '_ {
+        let mut state = vec![];
+        self.entries.iter().map(move |entry| {
+            match entry {
+                Entry::Full(s) => state = s.clone(),
+                Entry::Delta(delta) => apply(&mut state, delta),
+            }
+            state.clone()
+        })
+    }
+
+    // drop entries beyond `max_depth`, the new front becomes a full snapshot
+    fn truncate(&mut self) {
+        let Some(max_depth) = self.max_depth else {
+            return;
+        };
+        while self.entries.len() > max_depth {
+            let Some(Entry::Full(mut state)) = self.entries.pop_front() else {
+                unreachable!("front of history is always a full snapshot")
+            };
+            self.dropped += 1;
+            if let Some(front) = self.entries.front_mut() {
+                if let Entry::Delta(delta) = front {
+                    apply(&mut state, delta);
+                    *front = Entry::Full(state);
+                }
+            } else {
+                self.last.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // a state sequence where few signals change per step
+    fn state(n: Signal) -> Vec<Signal> {
+        vec![n, n / 3, n / 7, 42]
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut history = History::new(4, None);
+        for n in 0..20 {
+            history.push(&state(n));
+        }
+        assert_eq!(history.len(), 20);
+        for n in 0..20 {
+            assert_eq!(history.get(n as usize), Some(state(n)));
+        }
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            (0..20).map(state).collect::<Vec<_>>()
+        );
+
+        for n in (10..20).rev() {
+            assert_eq!(history.pop(), Some(state(n)));
+        }
+        // push after pop continues from the correct state
+        history.push(&state(100));
+        assert_eq!(history.get(10), Some(state(100)));
+        assert_eq!(history.get(9), Some(state(9)));
+        assert_eq!(history.get(11), None);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let mut history = History::new(4, Some(5));
+        for n in 0..20 {
+            history.push(&state(n));
+        }
+        assert_eq!(history.len(), 20);
+        assert_eq!(history.depth(), 5);
+        assert_eq!(history.dropped(), 15);
+        assert_eq!(history.get(14), None);
+        for n in 15..20 {
+            assert_eq!(history.get(n as usize), Some(state(n)));
+        }
+
+        // stepping back is limited to the kept entries
+        for n in (15..20).rev() {
+            assert_eq!(history.pop(), Some(state(n)));
+        }
+        assert_eq!(history.pop(), None);
+        assert_eq!(history.len(), 15);
+
+        history.set_max_depth(Some(2));
+        for n in 0..3 {
+            history.push(&state(n));
+        }
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![state(1), state(2)]);
+    }
+}
@@ -0,0 +1,392 @@
+// Model includes, composing a model of shared model files
+//
+// A model may list files to include (relative to the including file), e.g.,
+// `"include": [{"file": "regfile.json", "prefix": "rf", "offset": [200, 0]}]`.
+// The components of an included model are merged into the model, with ids
+// prefixed (`rf.regs`) and positions offset. Inputs between the included
+// components are renamed accordingly, while inputs from other components
+// (e.g., the wiring of the register file) connect to the including model.
+// An included model resolves its own parameters, the `parameters` of the
+// include overriding these. The files of its sub-circuits are relative to
+// the included file.
+use crate::common::{Component, Id, Input, SimError};
+use crate::{format::FileFormat, migrate, parameters};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct Include {
+    pub file: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<Id>,
+    #[serde(default)]
+    pub offset: (f32, f32),
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub parameters: Map<String, Value>,
+}
+
+// A model (as JSON) with its includes merged
+pub(crate) struct Model {
+    pub value: Value,
+    // the file each component came from, and its index in that file
+    pub origins: Vec<(PathBuf, usize)>,
+}
+
+// Renaming of the components (as JSON) of a model merged into another, by an
+// include or a `SubCircuit`
+pub(crate) struct Rename<'a> {
+    pub prefix: &'a str,
+    pub offset: (f32, f32),
+    // the ids to prefix, `None` for all
+    pub ids: Option<HashSet<String>>,
+    // ids replaced by inputs (the input ports of a `SubCircuit`)
+    pub ports: HashMap<&'a str, &'a Input>,
+}
+
+impl Rename<'_> {
+    fn id(&self, id: &str) -> String {
+        match self.prefix {
+            "" => id.into(),
+            prefix => format!("{}.{}", prefix, id),
+        }
+    }
+
+    /// prefix the id and offset the position of component `c`, renaming the
+    /// inputs declared in its ports
+    pub fn component(&self, c: &mut Value) {
+        let sub_circuit = c.get("type") == Some(&Value::from("SubCircuit"));
+        // other fields (e.g., of the component state) are kept as is, also
+        // if these happen to look like an input (unless equal to a declared
+        // input)
+        let declared = match sub_circuit {
+            true => vec![],
+            false => serde_json::from_value::<Box<dyn Component>>(c.clone())
+                .map(|c| c.get_id_ports().1.inputs)
+                .unwrap_or_default(),
+        };
+        let Value::Object(c) = c else {
+            return;
+        };
+        for (key, value) in c.iter_mut() {
+            match key.as_str() {
+                "id" => *value = self.id(value.as_str().unwrap_or_default()).into(),
+                "pos" => {
+                    if let Value::Array(pos) = value {
+                        for (p, offset) in pos.iter_mut().zip([self.offset.0, self.offset.1]) {
+                            *p = (p.as_f64().unwrap_or_default() + offset as f64).into();
+                        }
+                    }
+                }
+                // a nested sub-circuit is renamed when flattened itself,
+                // except for its connected inputs (`inputs[..].input`)
+                "inputs" if sub_circuit => {
+                    for port in value.as_array_mut().into_iter().flatten() {
+                        if let Some(input) = port.get_mut("input") {
+                            self.connect(input, None);
+                        }
+                    }
+                }
+                _ if sub_circuit => {}
+                _ => self.connect(value, Some(&declared)),
+            }
+        }
+    }
+
+    // rename the inputs (`{"id": .., "field": ..}`) found in `value`, the
+    // `declared` ones if given, an input port is replaced by the connected
+    // input
+    fn connect(&self, value: &mut Value, declared: Option<&[Input]>) {
+        match as_input(value) {
+            Some(input) if declared.is_none_or(|declared| declared.contains(&input)) => {
+                // unconnected inputs (no id) are kept as is
+                let renamed = match self.ports.get(input.id.as_str()) {
+                    Some(connected) => (*connected).clone(),
+                    None if input.id.is_empty() => return,
+                    None => match &self.ids {
+                        Some(ids) if !ids.contains(&input.id) => return,
+                        _ => Input::new(&self.id(&input.id), &input.field),
+                    },
+                };
+                *value = serde_json::to_value(renamed).unwrap();
+            }
+            _ => match value {
+                Value::Object(map) => map.values_mut().for_each(|v| self.connect(v, declared)),
+                Value::Array(values) => values.iter_mut().for_each(|v| self.connect(v, declared)),
+                _ => {}
+            },
+        }
+    }
+}
+
+// resolve the relative files of the sub-circuits in `store` (also of
+// embedded circuits) against `dir`, the directory of the model file
+fn locate_circuits(store: Option<&mut Value>, dir: &Path) {
+    for c in store.and_then(Value::as_array_mut).into_iter().flatten() {
+        if c.get("type") != Some(&Value::from("SubCircuit")) {
+            continue;
+        }
+        let Some(Value::Object(circuit)) = c.get_mut("circuit") else {
+            continue;
+        };
+        if let Some(Value::String(file)) = circuit.get_mut("File") {
+            if Path::new(file).is_relative() {
+                *file = dir.join(&*file).to_string_lossy().into();
+            }
+        }
+        if let Some(store) = circuit.get_mut("Store") {
+            locate_circuits(store.get_mut("store"), dir);
+        }
+    }
+}
+
+// `value` as an `Input`, if it has exactly the fields of one
+fn as_input(value: &Value) -> Option<Input> {
+    match value {
+        Value::Object(map) if map.len() == 2 => serde_json::from_value(value.clone()).ok(),
+        _ => None,
+    }
+}
+
+fn parse_err(path: &str, msg: impl ToString) -> SimError {
+    SimError::Parse {
+        path: path.into(),
+        msg: msg.to_string(),
+    }
+}
+
+/// load `value`, the model of `file` (upgraded to the current version) with
+/// its parameters overridden, merging its includes, `loading` holds the
+/// files being loaded
+pub(crate) fn load(
+    mut value: Value,
+    file: &Path,
+    overrides: Map<String, Value>,
+    loading: &mut Vec<PathBuf>,
+) -> Result<Model, SimError> {
+    migrate::upgrade(&mut value)?;
+    if let (Some(model), false) = (value.as_object_mut(), overrides.is_empty()) {
+        match model.get_mut("parameters") {
+            Some(Value::Object(defined)) => defined.extend(overrides),
+            _ => {
+                model.insert("parameters".into(), overrides.into());
+            }
+        }
+    }
+    parameters::resolve(&mut value)?;
+    // absolute, so that the model may be saved elsewhere
+    if let Some(dir) = fs::canonicalize(file)
+        .ok()
+        .as_deref()
+        .and_then(Path::parent)
+    {
+        locate_circuits(value.get_mut("store"), dir);
+    }
+
+    let includes: Vec<Include> = match value.as_object_mut().and_then(|m| m.remove("include")) {
+        Some(includes) => serde_path_to_error::deserialize(includes)
+            .map_err(|err| parse_err(&format!("include{}", err.path()), err.inner()))?,
+        None => vec![],
+    };
+    let count = value["store"].as_array().map_or(0, Vec::len);
+    let mut origins: Vec<(PathBuf, usize)> = (0..count).map(|i| (file.into(), i)).collect();
+
+    for (i, include) in includes.into_iter().enumerate() {
+        let path = file.parent().unwrap_or(Path::new("")).join(&include.file);
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if loading.contains(&canonical) {
+            return Err(parse_err(
+                &format!("include[{}]", i),
+                format!("{} includes itself", path.display()),
+            ));
+        }
+        let text = fs::read_to_string(&path).map_err(|error| SimError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        loading.push(canonical);
+        let included = FileFormat::from_path(&path)
+            .parse(&text)
+            .and_then(|model| load(model, &path, include.parameters, loading))
+            .map_err(|err| match err {
+                SimError::Parse { path: at, msg } => SimError::Parse {
+                    path: format!("{}: {}", path.display(), at),
+                    msg,
+                },
+                err => err,
+            })?;
+        loading.pop();
+
+        let mut store = match included.value.get("store") {
+            Some(Value::Array(store)) => store.clone(),
+            _ => vec![],
+        };
+        let rename = Rename {
+            prefix: include.prefix.as_deref().unwrap_or_default(),
+            offset: include.offset,
+            ids: Some(
+                store
+                    .iter()
+                    .filter_map(|c| c["id"].as_str())
+                    .map(Into::into)
+                    .collect(),
+            ),
+            ports: HashMap::new(),
+        };
+        store.iter_mut().for_each(|c| rename.component(c));
+        match value.get_mut("store") {
+            Some(Value::Array(merged)) => merged.extend(store),
+            _ => value["store"] = store.into(),
+        }
+        origins.extend(included.origins);
+    }
+    Ok(Model { value, origins })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{ComponentStore, Simulator};
+
+    // write `json` to file `name` of a test directory
+    fn write(dir: &str, name: &str, json: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(dir);
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, json).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_include() {
+        // an incrementer of `x.out` by `N`
+        write(
+            "syncrim_test_include",
+            "lib/inc.json",
+            r#"{
+                "parameters": {"N": 1},
+                "store": [
+                    {"type": "Constant", "id": "n", "pos": [0, 10], "value": "$N"},
+                    {
+                        "type": "Add",
+                        "id": "add",
+                        "pos": [0, 0],
+                        "a_in": {"id": "n", "field": "out"},
+                        "b_in": {"id": "x", "field": "out"}
+                    }
+                ]
+            }"#,
+        );
+        let path = write(
+            "syncrim_test_include",
+            "model.json",
+            r#"{
+                "parameters": {"M": 2},
+                "include": [
+                    {"file": "lib/inc.json", "prefix": "a"},
+                    {"file": "lib/inc.json", "prefix": "b", "offset": [100, 0], "parameters": {"N": "$M"}}
+                ],
+                "store": [{"type": "Constant", "id": "x", "pos": [0, 0], "value": 10}]
+            }"#,
+        );
+        let cs = ComponentStore::load_file(&path).unwrap();
+        let mut clock = 0;
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
+        assert_eq!(simulator.get_input_val(&Input::new("a.add", "out")), 11);
+        assert_eq!(simulator.get_input_val(&Input::new("b.add", "out")), 12);
+        let n = cs
+            .store
+            .iter()
+            .find(|c| c.get_id_ports().0 == "b.n")
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&**n).unwrap()["pos"],
+            serde_json::json!([100.0, 10.0])
+        );
+    }
+
+    #[test]
+    fn test_rename() {
+        let rename = Rename {
+            prefix: "p",
+            offset: (0.0, 0.0),
+            ids: None,
+            ports: HashMap::new(),
+        };
+        // only the declared input `r_in` is renamed
+        let mut c = serde_json::json!({
+            "type": "Register",
+            "id": "r",
+            "pos": [0, 0],
+            "r_in": {"id": "x", "field": "out"},
+            "note": {"id": "y", "field": "out"}
+        });
+        rename.component(&mut c);
+        assert_eq!(c["id"], "p.r");
+        assert_eq!(c["r_in"], serde_json::json!({"id": "p.x", "field": "out"}));
+        assert_eq!(c["note"], serde_json::json!({"id": "y", "field": "out"}));
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let lib = r#"{"store": [{"type": "Constant", "id": "x", "pos": [0, 0], "value": 1}]}"#;
+        write("syncrim_test_include_errors", "lib/x.json", lib);
+        let load = |json: &str| {
+            let path = write("syncrim_test_include_errors", "model.json", json);
+            ComponentStore::load_file(&path).err().unwrap().to_string()
+        };
+        let dir = std::env::temp_dir().join("syncrim_test_include_errors");
+
+        assert_eq!(
+            load(
+                r#"{
+                    "include": [{"file": "lib/x.json"}],
+                    "store": [{"type": "Constant", "id": "x", "pos": [0, 0], "value": 1}]
+                }"#
+            ),
+            format!(
+                "Component identifier \"x\" is defined twice, in {:?} and {:?}",
+                dir.join("model.json"),
+                dir.join("lib/x.json")
+            )
+        );
+        assert_eq!(
+            load(r#"{"include": [{"file": "model.json"}], "store": []}"#),
+            format!(
+                "Parse error at include[0]: {} includes itself",
+                dir.join("model.json").display()
+            )
+        );
+
+        // errors locate the included file
+        write(
+            "syncrim_test_include_errors",
+            "lib/bad.json",
+            r#"{"store": [{"type": "Constant", "id": "x", "pos": [0, 0], "value": "$N"}]}"#,
+        );
+        assert_eq!(
+            load(r#"{"include": [{"file": "lib/bad.json"}], "store": []}"#),
+            format!(
+                "Parse error at {}: store[0].value: Unknown parameter \"N\" in \"$N\"",
+                dir.join("lib/bad.json").display()
+            )
+        );
+        write(
+            "syncrim_test_include_errors",
+            "lib/bad.json",
+            r#"{"store": [{"type": "Constant", "id": "x", "pos": [0, 0], "value": -1}]}"#,
+        );
+        assert_eq!(
+            load(r#"{"include": [{"file": "lib/bad.json"}], "store": []}"#),
+            format!(
+                "Parse error at {}: store[0].value: invalid value: integer `-1`, expected u64",
+                dir.join("lib/bad.json").display()
+            )
+        );
+    }
+}
@@ -1,7 +1,27 @@
+// Allows the code generated by `syncrim-derive` (`::syncrim::..`) within
+// this crate
+extern crate self as syncrim;
+
+pub mod breakpoint;
+pub mod checkpoint;
+pub mod cli;
 pub mod common;
 pub mod component_store;
 pub mod fern;
+// Models shared by the tests
+#[cfg(test)]
+mod fixtures;
+pub mod format;
+pub mod history;
+pub mod include;
+pub mod lint;
+pub mod migrate;
+pub mod parameters;
+pub mod player;
+pub mod schema;
 pub mod simulator;
+pub mod trace;
+pub mod vcd;
 
 // Default provided components
 #[cfg(feature = "components")]
@@ -18,3 +38,7 @@ pub mod gui_egui;
 // Re-export
 #[cfg(feature = "gui-vizia")]
 pub use vizia;
+
+// Re-export, used by `component_schema!` and `#[component]`
+pub use inventory;
+pub use schemars;
@@ -0,0 +1,442 @@
+// Static checks of a model (a linter), reporting problems such as inputs
+// from unknown components, outputs never read or components missing a
+// position, without running the model.
+use crate::common::{ComponentStore, Id, Input, Signal, Width};
+#[cfg(feature = "components")]
+use crate::components::{Add, Mux, Sext};
+use serde_json::Value;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    // The model could not be built (e.g., duplicate ids or loops)
+    Build(String),
+    // An input without a driving component (empty id)
+    UnconnectedInput(Input),
+    // An input from a component that does not exist
+    UnknownComponent(Input),
+    // An input from a field that does not exist
+    UnknownField(Input),
+    // An output not read by any component
+    UnreadOutput(Id),
+    // The component has no position (`pos`)
+    NoPosition,
+    // `Sext` sizes outside of 1 ..= out_size ..= Signal::BITS
+    SextSize {
+        in_size: u64,
+        out_size: u64,
+    },
+    // `Add` width outside 1 ..= Signal::BITS
+    AddWidth(u32),
+    // A `Mux` select of `width` bits cannot address all `inputs`
+    MuxSelect {
+        width: u32,
+        inputs: usize,
+    },
+    // A `Mux` select of `width` bits, of which only the low `used` bits
+    // address the inputs
+    MuxSelectWide {
+        width: u32,
+        used: u32,
+    },
+    // An input of `input_width` bits driven by an output of `width` bits
+    Width {
+        input: Input,
+        width: u32,
+        input_width: u32,
+    },
+    // An input field of the component not declared in its ports
+    UndeclaredInput(Input),
+}
+
+// A problem of component `id` (empty for problems of the model)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub id: Id,
+    pub kind: Kind,
+}
+
+impl Problem {
+    fn new(id: &str, kind: Kind) -> Self {
+        Problem {
+            id: id.into(),
+            kind,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            Kind::UnreadOutput(_)
+            | Kind::NoPosition
+            | Kind::MuxSelect { .. }
+            | Kind::MuxSelectWide { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Build(err) => write!(f, "{}", err),
+            Kind::UnconnectedInput(input) => write!(f, "input {:?} is not connected", input.field),
+            Kind::UnknownComponent(input) => {
+                write!(f, "input {} from unknown component {:?}", input, input.id)
+            }
+            Kind::UnknownField(input) => {
+                write!(f, "input {} from unknown field {:?}", input, input.field)
+            }
+            Kind::UnreadOutput(field) => write!(f, "output {:?} is never read", field),
+            Kind::NoPosition => write!(f, "has no position"),
+            Kind::SextSize { in_size, out_size } => write!(
+                f,
+                "sign extension from {} to {} bits, expected 0 < in_size <= out_size <= {}",
+                in_size,
+                out_size,
+                Signal::BITS
+            ),
+            Kind::AddWidth(width) => write!(
+                f,
+                "addition of {} bits, expected 1 ..= {}",
+                width,
+                Signal::BITS
+            ),
+            Kind::MuxSelect { width, inputs } => write!(
+                f,
+                "select of {} bit(s) cannot address all {} inputs",
+                width, inputs
+            ),
+            Kind::MuxSelectWide { width, used } => write!(
+                f,
+                "select of {} bits, only the low {} bit(s) address the inputs",
+                width, used
+            ),
+            Kind::Width {
+                input,
+                width,
+                input_width,
+            } => write!(
+                f,
+                "{} bit input driven by {} of {} bits",
+                input_width, input, width
+            ),
+            Kind::UndeclaredInput(input) => {
+                write!(f, "input {} not declared in its ports", input)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity())?;
+        if !self.id.is_empty() {
+            write!(f, "{}: ", self.id)?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+/// check the model, returning the problems found, errors first
+pub fn check(cs: &ComponentStore) -> Vec<Problem> {
+    let mut problems = vec![];
+    // sub-circuits are checked as flattened
+    let cs = &match cs.flatten() {
+        Ok(cs) => cs,
+        Err(err) => return vec![Problem::new("", Kind::Build(err.to_string()))],
+    };
+
+    // components as serialized, giving access to their fields
+    let components: Vec<(Id, Value)> = cs
+        .store
+        .iter()
+        .map(|c| {
+            let value = serde_json::to_value(&**c).unwrap_or_default();
+            (c.get_id_ports().0, value)
+        })
+        .collect();
+    // outputs, with their widths
+    let outputs: HashMap<(Id, Id), Width> = cs
+        .store
+        .iter()
+        .flat_map(|c| {
+            let (id, ports) = c.get_id_ports();
+            ports
+                .outputs
+                .into_iter()
+                .zip(ports.output_widths)
+                .map(move |(field, width)| ((id.clone(), field), width))
+        })
+        .collect();
+    let ids: HashSet<&Id> = components.iter().map(|(id, _)| id).collect();
+
+    // inputs
+    let mut read = HashSet::new();
+    for c in &cs.store {
+        let (id, ports) = c.get_id_ports();
+        for (input, input_width) in ports.inputs.into_iter().zip(ports.input_widths) {
+            let output = (input.id.clone(), input.field.clone());
+            let kind = if input.id.is_empty() {
+                Kind::UnconnectedInput(input)
+            } else if !ids.contains(&input.id) {
+                Kind::UnknownComponent(input)
+            } else if !outputs.contains_key(&output) {
+                Kind::UnknownField(input)
+            } else {
+                let width = outputs[&output];
+                read.insert(output);
+                match (width, input_width) {
+                    (Some(width), Some(input_width)) if width > input_width => Kind::Width {
+                        input,
+                        width,
+                        input_width,
+                    },
+                    _ => continue,
+                }
+            };
+            problems.push(Problem::new(&id, kind));
+        }
+    }
+
+    for c in &cs.store {
+        let (id, ports) = c.get_id_ports();
+        for field in ports.outputs {
+            if !read.contains(&(id.clone(), field.clone())) {
+                problems.push(Problem::new(&id, Kind::UnreadOutput(field)));
+            }
+        }
+    }
+
+    // inputs of the component (as serialized) not declared in its ports,
+    // these are not connected when the model is built
+    for (c, (id, value)) in cs.store.iter().zip(&components) {
+        let declared = c.get_id_ports().1.inputs;
+        let mut inputs = vec![];
+        find_inputs(value, &mut inputs);
+        for input in inputs {
+            if !declared.contains(&input) {
+                problems.push(Problem::new(id, Kind::UndeclaredInput(input)));
+            }
+        }
+    }
+
+    // component specific checks
+    for (c, (id, value)) in cs.store.iter().zip(&components) {
+        if value.get("pos").is_none() {
+            problems.push(Problem::new(id, Kind::NoPosition));
+        }
+        if let Some(kind) = check_component((**c).as_any(), &outputs) {
+            problems.push(Problem::new(id, kind));
+        }
+    }
+
+    problems.sort_by_key(|p| std::cmp::Reverse(p.severity()));
+    problems
+}
+
+// checks of the provided components, by type
+#[cfg(feature = "components")]
+fn check_component(c: &dyn Any, outputs: &HashMap<(Id, Id), Width>) -> Option<Kind> {
+    if let Some(sext) = c.downcast_ref::<Sext>() {
+        let (in_size, out_size) = (sext.in_size as u64, sext.out_size as u64);
+        if in_size == 0 || in_size > out_size || out_size > Signal::BITS as u64 {
+            return Some(Kind::SextSize { in_size, out_size });
+        }
+    } else if let Some(add) = c.downcast_ref::<Add>() {
+        if add.width == 0 || add.width > Signal::BITS {
+            return Some(Kind::AddWidth(add.width));
+        }
+    } else if let Some(mux) = c.downcast_ref::<Mux>() {
+        let inputs = mux.m_in.len();
+        let select = (mux.select.id.clone(), mux.select.field.clone());
+        if let Some(Some(width)) = outputs.get(&select) {
+            let width = *width;
+            let used = mux.select_width();
+            if width < usize::BITS && (1 << width) < inputs {
+                return Some(Kind::MuxSelect { width, inputs });
+            } else if width > used {
+                return Some(Kind::MuxSelectWide { width, used });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(not(feature = "components"))]
+fn check_component(_c: &dyn Any, _outputs: &HashMap<(Id, Id), Width>) -> Option<Kind> {
+    None
+}
+
+// collect the inputs, objects of exactly an `id` and a `field`, of a
+// serialized component
+fn find_inputs(value: &Value, inputs: &mut Vec<Input>) {
+    match value {
+        Value::Object(map) => {
+            if map.len() == 2 {
+                if let Ok(input) = serde_json::from_value::<Input>(value.clone()) {
+                    inputs.push(input);
+                    return;
+                }
+            }
+            map.values().for_each(|value| find_inputs(value, inputs));
+        }
+        Value::Array(values) => values.iter().for_each(|value| find_inputs(value, inputs)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{component, Component, ComponentPorts};
+    use crate::components::*;
+    use serde::{Deserialize, Serialize};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_check() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("po")),
+                Rc::new(Add {
+                    id: "add".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("po", "out"),
+                    b_in: Input::new("po", "missing"),
+                    width: 32,
+                }),
+                Rc::new(Add {
+                    id: "add2".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("po", "out"),
+                    b_in: Input::new("po", "out"),
+                    width: 65,
+                }),
+                Rc::new(Wire {
+                    id: "w".into(),
+                    pos: (0.0, 0.0),
+                    delta: (10.0, 0.0),
+                    input: Input::new("gone", "out"),
+                }),
+                Rc::new(Sext {
+                    id: "sext".into(),
+                    pos: (0.0, 0.0),
+                    sext_in: Input::new("add", "out"),
+                    in_size: 16,
+                    out_size: 8,
+                }),
+                Rc::new(Mux {
+                    id: "mux".into(),
+                    pos: (0.0, 0.0),
+                    select: Input::new("add", "overflow"),
+                    m_in: vec![Input::new("sext", "out"); 3],
+                }),
+                Rc::new(Mux {
+                    id: "mux2".into(),
+                    pos: (0.0, 0.0),
+                    select: Input::new("sext", "out"),
+                    m_in: vec![Input::new("sext", "out"); 2],
+                }),
+            ],
+        };
+        let problems: Vec<String> = check(&cs).iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            problems,
+            vec![
+                "error: add: input po.missing from unknown field \"missing\"",
+                "error: w: input gone.out from unknown component \"gone\"",
+                "error: add2: addition of 65 bits, expected 1 ..= 64",
+                "error: sext: sign extension from 16 to 8 bits, expected 0 < in_size <= out_size <= 64",
+                "warning: add2: output \"out\" is never read",
+                "warning: add2: output \"overflow\" is never read",
+                "warning: mux: output \"out\" is never read",
+                "warning: mux2: output \"out\" is never read",
+                "warning: po: has no position",
+                "warning: mux: select of 1 bit(s) cannot address all 3 inputs",
+                "warning: mux2: select of 8 bits, only the low 1 bit(s) address the inputs",
+            ]
+        );
+    }
+
+    // input field `hidden`, not declared in its ports
+    #[derive(Serialize, Deserialize, ComponentPorts)]
+    #[outputs(out)]
+    struct Hidden {
+        id: Id,
+        #[input]
+        a_in: Input,
+        hidden: Input,
+    }
+
+    #[component]
+    #[typetag::serde]
+    impl Component for Hidden {}
+
+    #[cfg(feature = "gui-vizia")]
+    #[typetag::serde]
+    impl crate::common::ViziaComponent for Hidden {}
+
+    #[cfg(feature = "gui-egui")]
+    #[typetag::serde]
+    impl crate::common::EguiComponent for Hidden {}
+
+    #[test]
+    fn test_undeclared() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("addr")),
+                Rc::new(ProbeOut::new("data")),
+                Rc::new(ProbeOut::new("ctrl")),
+                Rc::new(ProbeOut::new("size")),
+                Rc::new(ProbeOut::new("sign")),
+                // all inputs declared
+                Rc::new(Mem {
+                    id: "mem".into(),
+                    pos: (0.0, 0.0),
+                    width: 100.0,
+                    height: 10.0,
+                    big_endian: false,
+                    data: Input::new("data", "out"),
+                    addr: Input::new("addr", "out"),
+                    ctrl: Input::new("ctrl", "out"),
+                    sign: Input::new("sign", "out"),
+                    size: Input::new("size", "out"),
+                    memory: Memory::new(),
+                }),
+                Rc::new(Hidden {
+                    id: "hidden".into(),
+                    a_in: Input::new("mem", "data"),
+                    hidden: Input::new("size", "out"),
+                }),
+            ],
+        };
+        let problems: Vec<String> = check(&cs)
+            .iter()
+            .filter(|p| matches!(p.kind, Kind::UndeclaredInput(_)))
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            vec!["error: hidden: input size.out not declared in its ports"]
+        );
+    }
+}
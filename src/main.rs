@@ -1,26 +1,38 @@
 use clap::Parser;
+use log::*;
 use std::path::PathBuf;
-use syncrim::{common::ComponentStore, fern::fern_setup};
-
-/// Simple program to greet a person
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Path to the model to load on startup
-    #[arg(short, long)]
-    model: String,
-}
+use syncrim::{
+    cli::{self, Args},
+    common::ComponentStore,
+    fern::fern_setup,
+};
 
 fn main() {
-    fern_setup();
     let args = Args::parse();
-    let _path = PathBuf::from(args.model);
 
-    let _cs = ComponentStore::load_file(&_path);
+    match args.command {
+        Some(command) => std::process::exit(cli::execute(command)),
+        None => {
+            fern_setup();
+            let Some(model) = args.model else {
+                error!("No model given, use `--model <MODEL>` or a sub-command (see `--help`)");
+                std::process::exit(1);
+            };
+            let _path = PathBuf::from(model);
+
+            let _cs = match ComponentStore::load_file(&_path) {
+                Ok(cs) => cs,
+                Err(err) => {
+                    error!("Failed to load model: {}", err);
+                    std::process::exit(1);
+                }
+            };
 
-    #[cfg(feature = "gui-egui")]
-    syncrim::gui_egui::gui(&_cs, &_path).ok();
+            #[cfg(feature = "gui-egui")]
+            syncrim::gui_egui::gui(&_cs, &_path).ok();
 
-    #[cfg(feature = "gui-vizia")]
-    syncrim::gui_vizia::gui(&_cs, &_path);
+            #[cfg(feature = "gui-vizia")]
+            syncrim::gui_vizia::gui(&_cs, &_path);
+        }
+    }
 }
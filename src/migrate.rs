@@ -0,0 +1,100 @@
+// Format versions of model files, and the migrations upgrading older models
+//
+// Saved models hold the format `version` (models without one are version 0).
+// When the JSON of a component changes (e.g., a field is added or renamed), the
+// version is bumped and a migration is added, upgrading the JSON of models
+// saved with the previous version. Models are upgraded when loaded, and can be
+// rewritten by `syncrim convert`.
+use crate::common::SimError;
+use serde_json::{Map, Value};
+
+// The current format version
+pub const VERSION: u64 = 1;
+
+// Migration `i` upgrades a model of version `i` to version `i + 1`
+const MIGRATIONS: [fn(&mut Value); VERSION as usize] = [explicit_add_width];
+
+/// upgrade `model` to the current version, returns the version it had
+pub fn upgrade(model: &mut Value) -> Result<u64, SimError> {
+    let parse_err = |msg: String| SimError::Parse {
+        path: "version".into(),
+        msg,
+    };
+    let version = match model.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| parse_err(format!("invalid version {}", version)))?,
+    };
+    if version > VERSION {
+        return Err(parse_err(format!(
+            "version {} is newer than supported (version {})",
+            version, VERSION
+        )));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(model);
+    }
+    if let Some(model) = model.as_object_mut() {
+        model.insert("version".into(), VERSION.into());
+    }
+    Ok(version)
+}
+
+// apply `f` to each component of `value` of type `ty`, including those of
+// embedded models (e.g., of a `SubCircuit`)
+fn for_each_component(value: &mut Value, ty: &str, f: &impl Fn(&mut Map<String, Value>)) {
+    match value {
+        Value::Object(map) => {
+            if map.get("type").and_then(Value::as_str) == Some(ty) {
+                f(map);
+            }
+            map.values_mut().for_each(|v| for_each_component(v, ty, f));
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| for_each_component(v, ty, f)),
+        _ => {}
+    }
+}
+
+// 0 -> 1, `Add` has a width, 32 bits before
+fn explicit_add_width(model: &mut Value) {
+    for_each_component(model, "Add", &|add| {
+        add.entry("width").or_insert(32.into());
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_upgrade() {
+        let mut model = json!({"store": [
+            {"type": "Add", "id": "a"},
+            {"type": "Add", "id": "b", "width": 8},
+            {"type": "SubCircuit", "circuit": {"Store": {"store": [{"type": "Add", "id": "c"}]}}},
+        ]});
+        assert_eq!(upgrade(&mut model).unwrap(), 0);
+        assert_eq!(
+            model,
+            json!({"version": 1, "store": [
+                {"type": "Add", "id": "a", "width": 32},
+                {"type": "Add", "id": "b", "width": 8},
+                {"type": "SubCircuit", "circuit": {"Store": {"store": [{"type": "Add", "id": "c", "width": 32}]}}},
+            ]})
+        );
+        // current models are kept as is
+        let upgraded = model.clone();
+        assert_eq!(upgrade(&mut model).unwrap(), VERSION);
+        assert_eq!(model, upgraded);
+
+        assert_eq!(
+            upgrade(&mut json!({"version": 2, "store": []}))
+                .unwrap_err()
+                .to_string(),
+            "Parse error at version: version 2 is newer than supported (version 1)"
+        );
+        assert!(upgrade(&mut json!({"version": "1", "store": []})).is_err());
+    }
+}
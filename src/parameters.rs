@@ -0,0 +1,427 @@
+// Model parameters, named constants used by the fields of the components
+//
+// A model may start with a `parameters` section, mapping names to numbers or
+// expressions (e.g., `{"XLEN": 32, "WORD_BYTES": "$XLEN / 8"}`). Any string
+// value of the model starting with a `$` is an expression, replaced by its
+// value when the model is loaded (e.g., `"out_size": "$XLEN"`, `"pos": [100,
+// "$Y + 20"]`, `"value": "$(0x8000 + $XLEN)"`). Expressions use `+`, `-`,
+// `*`, `/`, `%` and parentheses on integers (decimal or `0x` hex) and
+// floats, with integer division if both operands are integers. Ids, fields,
+// types and file paths are not evaluated, an expression there is an error.
+use crate::common::SimError;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Num {
+    Int(i128),
+    Float(f64),
+}
+
+impl Num {
+    fn float(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(f) => f,
+        }
+    }
+
+    fn apply(self, op: char, rhs: Num) -> Result<Num, String> {
+        let overflow = || format!("Overflow in {:?} {} {:?}", self, op, rhs);
+        match (self, rhs) {
+            (Num::Int(a), Num::Int(b)) => {
+                if b == 0 && (op == '/' || op == '%') {
+                    return Err("Division by zero".into());
+                }
+                let result = match op {
+                    '+' => a.checked_add(b),
+                    '-' => a.checked_sub(b),
+                    '*' => a.checked_mul(b),
+                    '/' => a.checked_div(b),
+                    _ => a.checked_rem(b),
+                };
+                result.map(Num::Int).ok_or_else(overflow)
+            }
+            _ => {
+                let (a, b) = (self.float(), rhs.float());
+                Ok(Num::Float(match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a % b,
+                }))
+            }
+        }
+    }
+
+    fn to_value(self) -> Result<Value, String> {
+        match self {
+            Num::Int(i) => u64::try_from(i)
+                .map(Value::from)
+                .or_else(|_| i64::try_from(i).map(Value::from))
+                .map_err(|_| format!("{} is out of range", i)),
+            Num::Float(f) => serde_json::Number::from_f64(f)
+                .map(Value::Number)
+                .ok_or_else(|| format!("{} is not a number", f)),
+        }
+    }
+}
+
+// The parameters of a model, evaluated on first use
+struct Parameters {
+    definitions: Map<String, Value>,
+    values: HashMap<String, Num>,
+    // the parameters being evaluated, to detect cyclic definitions
+    evaluating: Vec<String>,
+    // the first faulty definition found
+    error: Option<SimError>,
+}
+
+impl Parameters {
+    fn get(&mut self, name: &str) -> Result<Num, String> {
+        if let Some(num) = self.values.get(name) {
+            return Ok(*num);
+        }
+        if self.evaluating.iter().any(|n| n == name) {
+            let msg = format!("Parameter {:?} is defined in terms of itself", name);
+            self.fail(name, msg.clone());
+            return Err(msg);
+        }
+        let num = match self.definitions.get(name).cloned() {
+            Some(Value::String(text)) => {
+                self.evaluating.push(name.into());
+                let num = eval(&text, self);
+                self.evaluating.pop();
+                num.map_err(|msg| {
+                    self.fail(name, format!("{} in {:?}", msg, text));
+                    msg
+                })?
+            }
+            Some(Value::Number(n)) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => Num::Int(i.into()),
+                (_, Some(u)) => Num::Int(u.into()),
+                _ => Num::Float(n.as_f64().unwrap_or_default()),
+            },
+            Some(_) => {
+                let msg = format!("Parameter {:?} is not a number", name);
+                self.fail(name, msg.clone());
+                return Err(msg);
+            }
+            None => return Err(format!("Unknown parameter {:?}", name)),
+        };
+        self.values.insert(name.into(), num);
+        Ok(num)
+    }
+
+    // report the definition of `name` as faulty, unless a (nested) definition
+    // already is
+    fn fail(&mut self, name: &str, msg: String) {
+        self.error.get_or_insert(SimError::Parse {
+            path: format!("parameters.{}", name),
+            msg,
+        });
+    }
+}
+
+// Recursive descent parser of an expression
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    // the next character, skipping whitespace
+    fn peek(&mut self) -> Option<char> {
+        self.take_while(char::is_whitespace);
+        self.text.chars().next()
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let end = self.text.find(|c| !f(c)).unwrap_or(self.text.len());
+        let (taken, rest) = self.text.split_at(end);
+        self.pos += end;
+        self.text = rest;
+        taken
+    }
+
+    // skip the (peeked) next character
+    fn advance(&mut self) {
+        let len = self.text.chars().next().map_or(0, char::len_utf8);
+        self.pos += len;
+        self.text = &self.text[len..];
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self, params: &mut Parameters) -> Result<Num, String> {
+        let mut num = self.term(params)?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.advance();
+            num = num.apply(op, self.term(params)?)?;
+        }
+        Ok(num)
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self, params: &mut Parameters) -> Result<Num, String> {
+        let mut num = self.unary(params)?;
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.advance();
+            num = num.apply(op, self.unary(params)?)?;
+        }
+        Ok(num)
+    }
+
+    // unary := '-' unary | '(' expr ')' | '$' name | '$' '(' expr ')' | number
+    fn unary(&mut self, params: &mut Parameters) -> Result<Num, String> {
+        match self.peek() {
+            Some('-') => {
+                self.advance();
+                Num::Int(0).apply('-', self.unary(params)?)
+            }
+            Some('(') => {
+                self.advance();
+                let num = self.expr(params)?;
+                match self.peek() {
+                    Some(')') => {
+                        self.advance();
+                        Ok(num)
+                    }
+                    _ => Err(self.expected("`)`")),
+                }
+            }
+            Some('$') => {
+                self.advance();
+                if self.text.starts_with('(') {
+                    return self.unary(params);
+                }
+                let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+                if name.is_empty() {
+                    return Err(self.expected("a parameter name"));
+                }
+                params.get(name)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let literal = self.take_while(|c| c.is_ascii_alphanumeric() || c == '.');
+                if let Some(hex) = literal.strip_prefix("0x") {
+                    i128::from_str_radix(hex, 16).map(Num::Int).ok()
+                } else if let Ok(i) = literal.parse() {
+                    Some(Num::Int(i))
+                } else {
+                    literal.parse().map(Num::Float).ok()
+                }
+                .ok_or_else(|| format!("Invalid number {:?}", literal))
+            }
+            _ => Err(self.expected("a number, parameter or `(`")),
+        }
+    }
+
+    fn expected(&self, what: &str) -> String {
+        format!("Expected {} at position {}", what, self.pos)
+    }
+}
+
+// evaluate the expression `text`
+fn eval(text: &str, params: &mut Parameters) -> Result<Num, String> {
+    let mut parser = Parser { text, pos: 0 };
+    let num = parser.expr(params)?;
+    match parser.peek() {
+        None => Ok(num),
+        Some(_) => Err(parser.expected("an operator")),
+    }
+}
+
+// fields naming components, ports, types or files, never evaluated
+const NAMES: [&str; 7] = ["type", "id", "field", "port", "name", "file", "File"];
+
+// replace the expressions found in `value`, `path` locates `value`
+fn replace(value: &mut Value, path: &str, params: &mut Parameters) -> Result<(), SimError> {
+    match value {
+        Value::String(text) if text.starts_with('$') => {
+            *value = eval(text, params).and_then(Num::to_value).map_err(|msg| {
+                params.error.take().unwrap_or_else(|| SimError::Parse {
+                    path: path.into(),
+                    msg: format!("{} in {:?}", msg, text),
+                })
+            })?;
+        }
+        Value::Array(values) => {
+            for (i, v) in values.iter_mut().enumerate() {
+                replace(v, &format!("{}[{}]", path, i), params)?;
+            }
+        }
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                let path = match path {
+                    "" => key.clone(),
+                    _ => format!("{}.{}", path, key),
+                };
+                match v {
+                    Value::String(text) if NAMES.contains(&key.as_str()) => {
+                        if text.starts_with('$') {
+                            return Err(SimError::Parse {
+                                path,
+                                msg: format!("Expression {:?} in an id, type or path", text),
+                            });
+                        }
+                    }
+                    _ => replace(v, &path, params)?,
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// remove the `parameters` section of `model` (if any), replacing the
+/// expressions of the model by their values
+pub fn resolve(model: &mut Value) -> Result<(), SimError> {
+    let definitions = match model.as_object_mut().and_then(|m| m.remove("parameters")) {
+        None => Map::new(),
+        Some(Value::Object(definitions)) => definitions,
+        Some(_) => {
+            return Err(SimError::Parse {
+                path: "parameters".into(),
+                msg: "expected a map of parameters".into(),
+            })
+        }
+    };
+    let mut params = Parameters {
+        definitions,
+        values: HashMap::new(),
+        evaluating: vec![],
+        error: None,
+    };
+    // unused parameters are checked as well
+    let names: Vec<String> = params.definitions.keys().cloned().collect();
+    for name in names {
+        if params.get(&name).is_err() {
+            return Err(params.error.take().unwrap());
+        }
+    }
+    replace(model, "", &mut params)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{ComponentStore, Input, Simulator};
+    use serde_json::json;
+
+    fn resolved(mut model: Value) -> Result<Value, String> {
+        resolve(&mut model)
+            .map(|_| model)
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_resolve() {
+        let model = json!({
+            "parameters": {
+                "WORD_BYTES": "$XLEN / 8",
+                "XLEN": 32,
+                "X": 10.5,
+            },
+            "store": [
+                {"id": "c", "value": "$WORD_BYTES", "pos": ["$X * 2", "$(-(1 + $XLEN) % 5)"]},
+                {"id": "s", "in_size": "0x10", "out_size": "$XLEN * 2 - 1", "label": "a $X"},
+            ]
+        });
+        assert_eq!(
+            resolved(model).unwrap(),
+            json!({
+                "store": [
+                    {"id": "c", "value": 4, "pos": [21.0, -3]},
+                    {"id": "s", "in_size": "0x10", "out_size": 63, "label": "a $X"},
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        for (parameters, msg) in [
+            (
+                json!({"A": "$B", "B": "$A"}),
+                "Parse error at parameters.A: Parameter \"A\" is defined in terms of itself",
+            ),
+            (
+                json!({"A": "$B * 2", "B": "1 +"}),
+                "Parse error at parameters.B: Expected a number, parameter or `(` at position 3 in \"1 +\"",
+            ),
+            (
+                json!({"A": "$C"}),
+                "Parse error at parameters.A: Unknown parameter \"C\" in \"$C\"",
+            ),
+            (
+                json!({"A": [1]}),
+                "Parse error at parameters.A: Parameter \"A\" is not a number",
+            ),
+        ] {
+            assert_eq!(resolved(json!({ "parameters": parameters })).unwrap_err(), msg);
+        }
+
+        for (value, msg) in [
+            ("$D", "Unknown parameter \"D\""),
+            ("$C / (1 - 1)", "Division by zero"),
+            ("$C +", "Expected a number, parameter or `(` at position 4"),
+            ("$($C", "Expected `)` at position 4"),
+            ("$C 1", "Expected an operator at position 3"),
+            ("$ + 1", "Expected a parameter name at position 1"),
+            ("$C + 1x", "Invalid number \"1x\""),
+        ] {
+            let model = json!({"parameters": {"C": 2}, "store": [{"value": value}]});
+            assert_eq!(
+                resolved(model).unwrap_err(),
+                format!("Parse error at store[0].value: {} in {:?}", msg, value)
+            );
+        }
+
+        // ids and paths are never evaluated
+        for (component, path) in [
+            (json!({"id": "$C"}), "store[0].id"),
+            (
+                json!({"in": {"id": "a", "field": "$C"}}),
+                "store[0].in.field",
+            ),
+            (
+                json!({"circuit": {"File": "$C.json"}}),
+                "store[0].circuit.File",
+            ),
+        ] {
+            let model = json!({"parameters": {"C": 2}, "store": [component]});
+            let err = resolved(model).unwrap_err();
+            assert!(
+                err.starts_with(&format!("Parse error at {}: Expression", path)),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let json = r#"{
+            "parameters": {"XLEN": 32, "HALF": "$XLEN / 2"},
+            "store": [
+                {"type": "Constant", "id": "c", "pos": [0, "$HALF"], "value": "$(0x8000 + $XLEN)"},
+                {
+                    "type": "Sext",
+                    "id": "sext",
+                    "pos": [0, 0],
+                    "sext_in": {"id": "c", "field": "out"},
+                    "in_size": "$HALF",
+                    "out_size": "$XLEN"
+                }
+            ]
+        }"#;
+        let cs = ComponentStore::load(json).unwrap();
+        let mut clock = 0;
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
+        assert_eq!(
+            simulator.get_input_val(&Input::new("sext", "out")),
+            0xffff_8020
+        );
+    }
+}
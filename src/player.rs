@@ -0,0 +1,192 @@
+// Continuous simulation (Play) for the frontends
+//
+// On each GUI update the player clocks the simulator as many times as due
+// for the selected rate, or as fast as possible up to a number of clocks per
+// update, stopping on breakpoint hits. In either case clocking stops after
+// `budget` time per update (keeping the GUI responsive on slow models).
+use crate::common::Simulator;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+// Time spent clocking per GUI update, at most
+pub const FRAME_BUDGET: Duration = Duration::from_millis(15);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rate {
+    Hz(f64),
+    // as fast as possible, at most `clocks` per update
+    Max { clocks: u64 },
+}
+
+// Rates to choose from in the frontends
+pub const RATES: [Rate; 7] = [
+    Rate::Hz(1.0),
+    Rate::Hz(10.0),
+    Rate::Hz(100.0),
+    Rate::Hz(1000.0),
+    Rate::Hz(10000.0),
+    Rate::Max { clocks: 1000 },
+    Rate::Max { clocks: u64::MAX },
+];
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rate::Hz(hz) if *hz >= 1000.0 => write!(f, "{} kHz", hz / 1000.0),
+            Rate::Hz(hz) => write!(f, "{} Hz", hz),
+            Rate::Max { clocks: u64::MAX } => write!(f, "Max"),
+            Rate::Max { clocks } => write!(f, "Max, {} / frame", clocks),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Player {
+    pub rate: Rate,
+    // time spent clocking per update, at most
+    pub budget: Duration,
+    // start of playing, and the number of clocks since
+    start: Instant,
+    clocks: u64,
+    // start of the measurement window, and the number of clocks at start
+    window: (Instant, u64),
+    // achieved clocks per second
+    pub achieved: f64,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player::new(Rate::Hz(10.0))
+    }
+}
+
+impl Player {
+    pub fn new(rate: Rate) -> Self {
+        let now = Instant::now();
+        Player {
+            rate,
+            budget: FRAME_BUDGET,
+            start: now,
+            clocks: 0,
+            window: (now, 0),
+            achieved: 0.0,
+        }
+    }
+
+    /// start playing at `now`
+    pub fn start(&mut self, now: Instant) {
+        self.start = now;
+        self.clocks = 0;
+        self.window = (now, 0);
+        self.achieved = 0.0;
+    }
+
+    /// change the rate, restarting at `now`
+    pub fn set_rate(&mut self, rate: Rate, now: Instant) {
+        self.rate = rate;
+        self.start(now);
+    }
+
+    /// clock the simulator as due at `now`, returns true on a breakpoint hit
+    pub fn update(&mut self, simulator: &mut Simulator, clock: &mut usize, now: Instant) -> bool {
+        let due = match self.rate {
+            Rate::Hz(hz) => {
+                let due = ((now - self.start).as_secs_f64() * hz) as u64;
+                // when falling behind, skip rather than catching up
+                let max_batch = hz.ceil() as u64;
+                if due > self.clocks + max_batch {
+                    // skipped clocks do not count as achieved
+                    let skipped = due - max_batch - self.clocks;
+                    self.clocks += skipped;
+                    self.window.1 += skipped;
+                }
+                due
+            }
+            Rate::Max { clocks } => self.clocks.saturating_add(clocks),
+        };
+        let started = Instant::now();
+        let mut hit = false;
+        while self.clocks < due && !hit && started.elapsed() < self.budget {
+            simulator.clock(clock);
+            self.clocks += 1;
+            hit = !simulator.hits.is_empty();
+        }
+
+        let (window_start, window_clocks) = self.window;
+        let elapsed = (now - window_start).as_secs_f64();
+        if elapsed >= 0.5 {
+            self.achieved = (self.clocks - window_clocks) as f64 / elapsed;
+            self.window = (now, self.clocks);
+        }
+        hit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::counter;
+
+    #[test]
+    fn test_rate() {
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        let start = Instant::now();
+        let mut player = Player::new(Rate::Hz(10.0));
+        player.start(start);
+
+        assert!(!player.update(
+            &mut simulator,
+            &mut clock,
+            start + Duration::from_millis(50)
+        ));
+        assert_eq!(clock, 1);
+        player.update(
+            &mut simulator,
+            &mut clock,
+            start + Duration::from_millis(550),
+        );
+        assert_eq!(clock, 6);
+        assert!((player.achieved - 5.0 / 0.55).abs() < 1e-9);
+
+        // falling behind, at most one second is caught up
+        player.update(&mut simulator, &mut clock, start + Duration::from_secs(10));
+        assert_eq!(clock, 16);
+    }
+
+    #[test]
+    fn test_max_break() {
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        simulator
+            .add_breakpoint("reg.out=1000".parse().unwrap())
+            .unwrap();
+        let mut player = Player::new(Rate::Max { clocks: 600 });
+        player.budget = Duration::from_secs(10);
+        player.start(Instant::now());
+
+        // at most 600 clocks per update
+        assert!(!player.update(&mut simulator, &mut clock, Instant::now()));
+        assert_eq!(clock, 601);
+        assert!(player.update(&mut simulator, &mut clock, Instant::now()));
+        assert_eq!(clock, 1001);
+        assert_eq!(Rate::Hz(10000.0).to_string(), "10 kHz");
+        assert_eq!(Rate::Max { clocks: 1000 }.to_string(), "Max, 1000 / frame");
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        let start = Instant::now();
+        let mut player = Player::new(Rate::Hz(10000.0));
+        player.budget = Duration::ZERO;
+        player.start(start);
+
+        // no time to clock, although clocks are due
+        player.update(&mut simulator, &mut clock, start + Duration::from_secs(1));
+        assert_eq!(clock, 1);
+    }
+}
@@ -0,0 +1,205 @@
+// JSON Schema of model files, for completion and validation in editors
+//
+// Component types are registered by `#[derive(ComponentSchema)]`, collecting
+// the schemas of all component types linked into the binary (e.g., those of
+// `mips` in a SyncRim-MIPS application). The schema of a component is that
+// of its struct, with the `type` tag added. The names of the components are
+// registered by `#[component]`, to find those without a schema. Number fields also accept the string
+// expressions of model parameters (see `parameters`).
+use crate::common::ComponentStore;
+use crate::include::Include;
+use schemars::{gen::SchemaGenerator, gen::SchemaSettings, schema::Schema, JsonSchema};
+use serde_json::{json, Value};
+
+// A component type and its schema, see `component_schema!`
+pub struct ComponentType {
+    // the `type` tag, the name of the struct
+    pub name: &'static str,
+    pub schema: fn(&mut SchemaGenerator) -> Schema,
+}
+
+inventory::collect!(ComponentType);
+
+// The name of a component type (its `type` tag), registered by `#[component]`
+pub struct ComponentName(pub &'static str);
+
+inventory::collect!(ComponentName);
+
+/// register the schema of component type `$ty` (deriving `JsonSchema`),
+/// done by `#[derive(ComponentSchema)]`
+#[macro_export]
+macro_rules! component_schema {
+    ($ty:ident) => {
+        $crate::inventory::submit! {
+            $crate::schema::ComponentType {
+                name: stringify!($ty),
+                schema: <$ty as $crate::schemars::JsonSchema>::json_schema,
+            }
+        }
+    };
+}
+
+// an embedded model (e.g., of a `SubCircuit`), a model itself
+impl JsonSchema for ComponentStore {
+    fn schema_name() -> String {
+        "ComponentStore".into()
+    }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Schema {
+        Schema::new_ref("#".into())
+    }
+
+    fn is_referenceable() -> bool {
+        false
+    }
+}
+
+/// the registered component types, sorted by name
+pub fn component_types() -> Vec<&'static ComponentType> {
+    let mut types: Vec<&ComponentType> = inventory::iter::<ComponentType>.into_iter().collect();
+    types.sort_by_key(|t| t.name);
+    types
+}
+
+/// the components (implemented with `#[component]`) without a registered
+/// schema, sorted by name, expected to be none
+pub fn unregistered_types() -> Vec<&'static str> {
+    let registered: Vec<&str> = component_types().iter().map(|t| t.name).collect();
+    let mut names: Vec<&str> = inventory::iter::<ComponentName>
+        .into_iter()
+        .map(|c| c.0)
+        .filter(|name| !registered.contains(name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// the JSON Schema of a model file, with the registered component types
+pub fn model_schema() -> Value {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let components: Vec<Value> = component_types()
+        .iter()
+        .map(|t| {
+            let mut schema = serde_json::to_value((t.schema)(&mut gen)).unwrap();
+            schema["properties"]["type"] = json!({ "const": t.name });
+            match schema["required"].as_array_mut() {
+                Some(required) => required.insert(0, "type".into()),
+                None => schema["required"] = json!(["type"]),
+            }
+            schema
+        })
+        .collect();
+    let mut include = serde_json::to_value(Include::json_schema(&mut gen)).unwrap();
+    let mut definitions = serde_json::to_value(gen.definitions()).unwrap();
+    definitions["Component"] = json!({ "oneOf": components });
+    expressions(&mut definitions);
+    expressions(&mut include);
+    definitions["Expression"] = json!({
+        "description": "An expression of model parameters, e.g., \"$XLEN / 8\"",
+        "type": "string",
+        "pattern": "^\\$",
+    });
+    let mut schema = json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "SyncRim model",
+        "type": "object",
+        "properties": {
+            "version": { "type": "integer", "minimum": 0 },
+            "parameters": {
+                "type": "object",
+                "additionalProperties": {
+                    "anyOf": [{ "type": "number" }, { "$ref": "#/definitions/Expression" }]
+                }
+            },
+            "include": { "type": "array", "items": include },
+            "store": { "type": "array", "items": { "$ref": "#/definitions/Component" } },
+        },
+        "required": ["store"],
+    });
+    schema["definitions"] = definitions;
+    schema
+}
+
+// allow expressions for the numbers of `schema`
+fn expressions(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            let number = map
+                .get("type")
+                .and_then(Value::as_str)
+                .is_some_and(|t| t == "integer" || t == "number");
+            if number {
+                let number = Value::Object(std::mem::take(map));
+                *schema = json!({ "anyOf": [number, { "$ref": "#/definitions/Expression" }] });
+            } else {
+                map.values_mut().for_each(expressions);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(expressions),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{component, Component, ComponentPorts, Id};
+    use serde::{Deserialize, Serialize};
+
+    // a component without `ComponentSchema`
+    #[derive(Serialize, Deserialize, ComponentPorts)]
+    #[outputs(out)]
+    struct Unregistered {
+        id: Id,
+    }
+
+    #[component]
+    #[typetag::serde]
+    impl Component for Unregistered {}
+
+    #[cfg(feature = "gui-vizia")]
+    #[typetag::serde]
+    impl crate::common::ViziaComponent for Unregistered {}
+
+    #[cfg(feature = "gui-egui")]
+    #[typetag::serde]
+    impl crate::common::EguiComponent for Unregistered {}
+
+    #[test]
+    fn test_model_schema() {
+        let names: Vec<&str> = component_types().iter().map(|t| t.name).collect();
+        for name in ["Add", "Constant", "Mem", "SubCircuit"] {
+            assert!(names.contains(&name), "{} not in {:?}", name, names);
+        }
+        // all components linked in are registered, but for the test
+        // components (the one above, of `lint` and `simulator`)
+        let unregistered = unregistered_types();
+        assert!(unregistered.contains(&"Unregistered"));
+        assert!(
+            unregistered
+                .iter()
+                .all(|name| ["Bit", "Hidden", "Unregistered"].contains(name)),
+            "{:?}",
+            unregistered
+        );
+
+        let schema = model_schema();
+        let add = schema["definitions"]["Component"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["properties"]["type"]["const"] == "Add")
+            .unwrap();
+        assert_eq!(add["required"][0], "type");
+        assert!(add["required"]
+            .as_array()
+            .unwrap()
+            .contains(&"width".into()));
+        assert_eq!(
+            add["properties"]["width"]["anyOf"][1]["$ref"],
+            "#/definitions/Expression"
+        );
+        assert_eq!(add["properties"]["a_in"]["$ref"], "#/definitions/Input");
+        assert!(schema["definitions"]["Input"].is_object());
+    }
+}
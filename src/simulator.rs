@@ -1,26 +1,67 @@
-use crate::common::{Component, ComponentStore, Id, Input, OutputType, Signal, Simulator};
+use crate::common::{
+    format_signal, width_mask, CombinatorialLoop, Component, ComponentStore, EvalMode, Id, Input,
+    OutputType, Signal, SimError, Simulator,
+};
+use crate::history::History;
 use petgraph::{
-    algo::toposort,
+    algo::{tarjan_scc, toposort},
     dot::{Config, Dot},
+    graph::{EdgeIndex, EdgeReference, NodeIndex},
+    visit::EdgeRef,
     Graph,
 };
 
 use log::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::{fs::File, io::prelude::*, path::PathBuf};
 
 pub struct IdComponent(pub HashMap<String, Box<dyn Component>>);
 
 // Notice:
-// The topological order does not enforce any specific order of registers
-// Thus registers cannot point to other registers in a cyclic fashion
-// This is (likely) not occurring in practice.
+// Sequential components (e.g., registers) have no incoming edges in the
+// dependency graph, thus the topological order does not enforce any order
+// between them. To give register to register paths a well defined meaning,
+// sequential components are evaluated in two phases on each clock:
+//
+// - sample: all sequential components read their inputs from the previous
+//   state, their outputs are buffered (not yet visible)
+// - commit: the buffered outputs are written to the simulation state
 //
-// A solution is to evaluate register updates separately from other components
-// ... but not currently implemented ...
+// after which the combinatorial components are evaluated in topological order.
 impl Simulator {
-    pub fn new(component_store: &ComponentStore, clock: &mut usize) -> Self {
+    pub fn new(component_store: &ComponentStore, clock: &mut usize) -> Result<Self, SimError> {
+        let mut simulator = Simulator::build(component_store)?;
+        simulator.clock(clock);
+        Ok(simulator)
+    }
+
+    /// build the simulator of a model to view (e.g., in a GUI), a model with
+    /// combinatorial loops is kept with its loops in `loops` (to highlight
+    /// them), but not evaluated
+    pub fn view(component_store: &ComponentStore, clock: &mut usize) -> Result<Self, SimError> {
+        let mut simulator = Simulator::build_keeping_loops(component_store, true)?;
+        if simulator.loops.is_empty() {
+            simulator.clock(clock);
+        } else {
+            error!("{}", SimError::Loop(simulator.loops.clone()));
+        }
+        Ok(simulator)
+    }
+
+    // build the simulator, without evaluating the reset state
+    pub(crate) fn build(component_store: &ComponentStore) -> Result<Self, SimError> {
+        Simulator::build_keeping_loops(component_store, false)
+    }
+
+    // build the simulator, a model with combinatorial loops is an error
+    // unless `keep_loops`, then its components are kept in store order
+    fn build_keeping_loops(
+        component_store: &ComponentStore,
+        keep_loops: bool,
+    ) -> Result<Self, SimError> {
+        let component_store = &component_store.flatten()?;
         let mut lens_values = vec![];
+        let mut widths = vec![];
 
         let mut id_start_index = HashMap::new();
         let mut id_component = HashMap::new(); // IdComponent(HashMap::new());
@@ -39,33 +80,45 @@ impl Simulator {
                 .insert(id.clone(), lens_values.len())
                 .is_some()
             {
-                panic!("Component identifier {:?} is defined twice", id);
+                return Err(SimError::DuplicateId(id));
             }
 
             id_component.insert(id.clone(), c);
 
             // create placeholder for output
+            let field_index: &mut HashMap<Id, usize> =
+                id_field_index.entry(id.clone()).or_default();
             #[allow(clippy::same_item_push)]
             for (index, field_id) in ports.outputs.iter().enumerate() {
                 // create the value with a default to 0
                 lens_values.push(0);
-                if id_field_index
-                    .insert((id.clone(), field_id.into()), index)
-                    .is_some()
-                {
-                    panic!("Component {:?} field {:?} is defined twice", id, field_id)
+                let width = ports.output_widths[index];
+                if let Some(width) = width.filter(|w| *w == 0 || *w > Signal::BITS) {
+                    return Err(SimError::OutputWidth {
+                        id,
+                        field: field_id.into(),
+                        width,
+                    });
+                }
+                widths.push(width);
+                if field_index.insert(field_id.into(), index).is_some() {
+                    return Err(SimError::DuplicateField {
+                        id,
+                        field: field_id.into(),
+                    });
                 };
             }
             id_nr_outputs.insert(id.clone(), ports.outputs.len());
         }
 
-        let mut graph = Graph::<_, (), petgraph::Directed>::new();
+        let mut graph = Graph::<_, _, petgraph::Directed>::new();
         let mut id_node = HashMap::new();
         let mut node_comp = HashMap::new();
 
-        // insert nodes
-        for (id, c) in &id_component {
-            let node = graph.add_node(id.to_owned());
+        // insert nodes (in store order, so loops are reported deterministically)
+        for c in &component_store.store {
+            let (id, _) = c.get_id_ports();
+            let node = graph.add_node(id.clone());
             id_node.insert(id, node);
             node_comp.insert(node, c);
         }
@@ -76,21 +129,50 @@ impl Simulator {
             trace!("node {:?}, comp_id {:?}", node, c.get_id_ports());
         }
 
-        // insert edges
-        for (to_id, c) in &id_component {
-            let to_component = id_component.get(to_id).unwrap();
-            let (_, ports) = to_component.get_id_ports();
+        // check that all inputs refer to existing components and fields,
+        // driven by outputs no wider than the input
+        for (id, c) in &id_component {
+            let (_, ports) = c.get_id_ports();
+            for (input, input_width) in ports.inputs.into_iter().zip(ports.input_widths) {
+                if !id_nr_outputs.contains_key(&input.id) {
+                    return Err(SimError::UnknownComponent {
+                        id: id.clone(),
+                        input,
+                    });
+                }
+                let Some(index) = id_field_index[&input.id].get(&input.field) else {
+                    return Err(SimError::UnknownField {
+                        id: id.clone(),
+                        input,
+                    });
+                };
+                let width = widths[id_start_index[&input.id] + index];
+                if let (Some(width), Some(input_width)) = (width, input_width) {
+                    if width > input_width {
+                        return Err(SimError::Width {
+                            id: id.clone(),
+                            input,
+                            width,
+                            input_width,
+                        });
+                    }
+                }
+            }
+        }
+
+        // insert edges, labeled by the output field driving the input
+        for c in &component_store.store {
+            let (to_id, ports) = c.get_id_ports();
 
             trace!("to_id :{}, ports: {:?}", to_id, ports);
 
             if ports.out_type == OutputType::Combinatorial {
-                let to_node = id_node.get(to_id).unwrap();
-                let (_, ports) = c.get_id_ports();
+                let to_node = id_node.get(&to_id).unwrap();
                 for in_port in &ports.inputs {
                     let from_id = &in_port.id;
 
                     let from_node = id_node.get(from_id).unwrap();
-                    graph.add_edge(*from_node, *to_node, ());
+                    graph.add_edge(*from_node, *to_node, in_port.field.clone());
                     trace!(
                         "add_edge {}:{:?} -> {}:{:?}",
                         from_id,
@@ -103,38 +185,114 @@ impl Simulator {
         }
 
         // topological order
-        let top =
-            toposort(&graph, None).expect("Topological sort failed, your model contains loops.");
+        let mut loops = vec![];
+        let top = match toposort(&graph, None) {
+            Ok(top) => top,
+            Err(_) if keep_loops => {
+                loops = find_loops(&graph);
+                graph.node_indices().collect()
+            }
+            Err(_) => return Err(SimError::Loop(find_loops(&graph))),
+        };
         trace!("--- top \n{:?}", top);
 
+        // sequential components go first, followed by the combinatorial ones
+        // (sequential components have no incoming edges so this respects the
+        // topological order)
         let mut ordered_components = vec![];
+        let mut combinatorial_components = vec![];
         for node in &top {
             // #[allow(clippy::clone_double_ref)] // old lint
             #[allow(suspicious_double_ref_op)] // changed in Rust 1.71
             let c = (**node_comp.get(node).unwrap()).clone();
-            ordered_components.push(c);
+            if c.get_id_ports().1.out_type == OutputType::Sequential {
+                ordered_components.push(c);
+            } else {
+                combinatorial_components.push(c);
+            }
         }
+        let nr_sequential = ordered_components.len();
+        ordered_components.append(&mut combinatorial_components);
 
         let component_ids: Vec<Id> = ordered_components
             .iter()
             .map(|c| c.get_id_ports().0)
             .collect();
 
-        let mut simulator = Simulator {
+        // resolve inputs and outputs to `sim_state` indexes (all inputs are
+        // checked above)
+        let input_index = ordered_components
+            .iter()
+            .map(|c| {
+                c.get_id_ports()
+                    .1
+                    .inputs
+                    .iter()
+                    .map(|input| {
+                        id_start_index[&input.id] + id_field_index[&input.id][&input.field]
+                    })
+                    .collect()
+            })
+            .collect();
+        let output_index = component_ids.iter().map(|id| id_start_index[id]).collect();
+
+        // the combinatorial components reading each output, for incremental
+        // evaluation
+        let position: HashMap<&Id, usize> = component_ids
+            .iter()
+            .enumerate()
+            .map(|(position, id)| (id, position))
+            .collect();
+        let mut fanout = vec![vec![]; lens_values.len()];
+        for edge in graph.edge_references() {
+            let from = &graph[edge.source()];
+            let index = id_start_index[from] + id_field_index[from][edge.weight()];
+            let to = position[&graph[edge.target()]];
+            if !fanout[index].contains(&to) {
+                fanout[index].push(to);
+            }
+        }
+        let parents = ordered_components
+            .iter()
+            .flat_map(|c| {
+                let id = c.get_id_ports().0;
+                c.sub_components()
+                    .into_iter()
+                    .map(move |sub_id| (sub_id, id.clone()))
+            })
+            .collect();
+        let dirty = vec![false; ordered_components.len()];
+        let masks = widths.iter().map(|width| width_mask(*width)).collect();
+
+        let simulator = Simulator {
             id_start_index,
             ordered_components,
+            nr_sequential,
+            sampled: None,
             id_nr_outputs,
             id_field_index,
             sim_state: lens_values,
-            history: vec![],
+            widths,
+            masks,
+            history: History::default(),
             component_ids,
+            input_index,
+            output_index,
+            current: 0,
+            eval_mode: EvalMode::Full,
+            fanout,
+            dirty,
+            four_state: false,
             graph,
+            breakpoints: vec![],
+            hits: vec![],
+            parents,
+            loops,
         };
 
         trace!("sim_state {:?}", simulator.sim_state);
 
-        simulator.clock(clock);
-        simulator
+        Ok(simulator)
     }
 
     /// get input by index
@@ -142,27 +300,97 @@ impl Simulator {
         self.sim_state[index]
     }
 
+    // index in `sim_state` of output `field` of component `id`
+    fn index(&self, id: &str, field: &str) -> Option<usize> {
+        let start_index = self.id_start_index.get(id)?;
+        let index = self.id_field_index.get(id)?.get(field)?;
+        Some(start_index + index)
+    }
+
     /// get input value
     pub fn get_input_val(&self, input: &Input) -> Signal {
-        let nr_out = *self.id_nr_outputs.get(&input.id).unwrap();
-        let index = *self
-            .id_field_index
-            .get(&(input.id.clone(), input.field.clone()))
-            .unwrap_or_else(|| {
-                panic!(
-                    "Component {:?}, field {:?} not found.",
-                    input.id, input.field
-                )
-            });
-        if index < nr_out {
-            let start_index = *self.id_start_index.get(&input.id).unwrap();
-            self.get(start_index + index)
-        } else {
+        let index = self.index(&input.id, &input.field).unwrap_or_else(|| {
             panic!(
-                "ICE: Attempt to read {:?} at index {}, where {:?} has only {} outputs.",
-                input.id, index, input.id, nr_out
+                "Component {:?}, field {:?} not found.",
+                input.id, input.field
             )
+        });
+        self.get(index)
+    }
+
+    /// get the value of input `port` (in `Ports::inputs` order) of the
+    /// component being evaluated, using the index resolved when building
+    pub fn get_input(&self, port: usize) -> Signal {
+        self.get(self.input_index[self.current][port])
+    }
+
+    /// get the unknown (X/Z) bits by index, always known (0) in two-state mode
+    pub(crate) fn get_xz(&self, index: usize) -> Signal {
+        // in four-state mode the unknown bits follow the values
+        self.sim_state
+            .get(self.widths.len() + index)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// get the unknown (X/Z) bits of input `port`, see `get_input`
+    pub fn get_input_xz(&self, port: usize) -> Signal {
+        self.get_xz(self.input_index[self.current][port])
+    }
+
+    /// the value of `input` as text, showing unknown bits (see `format_signal`)
+    pub fn format_input(&self, input: &Input) -> String {
+        let value = self.get_input_val(input);
+        // the input is found by `get_input_val`
+        let index = self.index(&input.id, &input.field).unwrap();
+        self.format_index(index, value)
+    }
+
+    // the value of `sim_state` index `index` as text
+    pub(crate) fn format_index(&self, index: usize, value: Signal) -> String {
+        format_signal(value, self.get_xz(index), self.widths[index])
+    }
+
+    /// get the index of an output in `sim_state`
+    pub fn get_input_index(&self, input: &Input) -> Result<usize, SimError> {
+        self.index(&input.id, &input.field)
+            .ok_or_else(|| SimError::UnknownSignal(input.clone()))
+    }
+
+    /// all outputs of the model, in evaluation order
+    pub fn outputs(&self) -> Vec<Input> {
+        self.ordered_components
+            .iter()
+            .flat_map(|c| {
+                let (id, ports) = c.get_id_ports();
+                ports
+                    .outputs
+                    .into_iter()
+                    .map(move |field| Input::new(&id, &field))
+            })
+            .collect()
+    }
+
+    /// the sub-circuits enclosing component `id`, innermost first
+    pub fn enclosing(&self, id: &str) -> Vec<Id> {
+        let mut enclosing = vec![];
+        let mut id = id;
+        while let Some(parent) = self.parents.get(id) {
+            enclosing.push(parent.clone());
+            id = parent;
         }
+        enclosing
+    }
+
+    /// the fields of component `id` closing a combinatorial loop (see
+    /// `Simulator::view`), empty if the component is not in a loop
+    pub fn loop_fields(&self, id: &str) -> Vec<&Id> {
+        self.loops
+            .iter()
+            .flat_map(|l| &l.0)
+            .filter(|input| input.id == id)
+            .map(|input| &input.field)
+            .collect()
     }
 
     /// get start index by id
@@ -170,39 +398,121 @@ impl Simulator {
         *self.id_start_index.get(id).unwrap()
     }
 
-    // set value by index
+    // set value by index (masked to its width), buffered during the sample
+    // phase, marking the components reading a changed value as dirty
     fn set(&mut self, index: usize, value: Signal) {
-        self.sim_state[index] = value;
+        let value = value & self.masks[index];
+        match &mut self.sampled {
+            Some(sampled) => sampled.push((index, value)),
+            None => {
+                if self.sim_state[index] != value {
+                    self.sim_state[index] = value;
+                    for position in &self.fanout[index] {
+                        self.dirty[*position] = true;
+                    }
+                }
+            }
+        }
     }
 
     /// set value by Id (instance) and Id (field)
     pub fn set_out_val(&mut self, id: &str, field: &str, value: Signal) {
-        let index = *self
-            .id_field_index
-            .get(&(id.into(), field.into()))
+        let index = self
+            .index(id, field)
             .unwrap_or_else(|| panic!("Component {}, field {} not found.", id, field));
-        let start_index = self.get_id_start_index(id);
-        self.set(start_index + index, value);
+        self.set_xz(index, value, 0);
+    }
+
+    /// set output `field` (in `Ports::outputs` order) of the component
+    /// being evaluated, using the index resolved when building, in
+    /// four-state mode the output is unknown (X) if any input is unknown,
+    /// components propagating unknown bits (e.g., `Add`) use `set_output_xz`
+    pub fn set_output(&mut self, field: usize, value: Signal) {
+        if self.four_state
+            && self.input_index[self.current]
+                .iter()
+                .any(|index| self.get_xz(*index) != 0)
+        {
+            self.set_output_xz(field, Signal::MAX, Signal::MAX);
+        } else {
+            self.set_output_xz(field, value, 0);
+        }
+    }
+
+    /// set output `field` with unknown bits `xz` (X where the value bit is
+    /// 1, Z where 0), the unknown bits are ignored in two-state mode
+    pub fn set_output_xz(&mut self, field: usize, value: Signal, xz: Signal) {
+        self.set_xz(self.output_index[self.current] + field, value, xz);
+    }
+
+    // set value and unknown bits by index
+    fn set_xz(&mut self, index: usize, value: Signal, xz: Signal) {
+        self.set(index, value);
+        if self.four_state {
+            self.set(self.widths.len() + index, xz);
+        }
     }
 
     /// iterate over the evaluators and increase clock by one
     pub fn clock(&mut self, clock: &mut usize) {
+        // a model with combinatorial loops is only viewed
+        if !self.loops.is_empty() {
+            return;
+        }
+        let dropped = self.history.dropped();
+        // the state before the first clock is not evaluated
+        let incremental = self.eval_mode == EvalMode::Incremental && !self.history.is_empty();
+        self.hits.clear();
+        let prev = (!self.breakpoints.is_empty()).then(|| self.sim_state.clone());
         // push current state
-        self.history.push(self.sim_state.clone());
+        self.history.push(&self.sim_state);
         let ordered_components = self.ordered_components.clone();
+        let (sequential, combinatorial) = ordered_components.split_at(self.nr_sequential);
 
-        for component in ordered_components {
+        // sample phase, sequential components read the previous state
+        self.sampled = Some(vec![]);
+        for (index, component) in sequential.iter().enumerate() {
+            self.current = index;
             component.clock(self);
         }
+
+        // commit phase, sequential outputs become visible
+        for (index, value) in self.sampled.take().unwrap() {
+            self.set(index, value);
+        }
+
+        for (index, component) in combinatorial.iter().enumerate() {
+            self.current = self.nr_sequential + index;
+            let dirty = std::mem::replace(&mut self.dirty[self.current], false);
+            if !incremental || dirty || component.always_evaluate() {
+                component.clock(self);
+            }
+        }
         *clock = self.history.len();
+
+        if let Some(prev) = prev {
+            self.check_breakpoints(&prev);
+        }
+        self.forget_dropped(dropped);
+    }
+
+    // let the components forget the internal state kept to un_clock the
+    // clocks dropped from history (since `dropped` clocks were dropped)
+    fn forget_dropped(&self, dropped: usize) {
+        if self.history.dropped() != dropped {
+            for component in &self.ordered_components {
+                component.forget_older(self.history.depth());
+            }
+        }
     }
 
-    /// reverse simulation using history if clock > 1
+    /// reverse simulation using history if clock > 1,
+    /// limited to the clocks kept in history
     pub fn un_clock(&mut self, clock: &mut usize) {
-        if *clock > 1 {
-            let state = self.history.pop().unwrap();
+        self.hits.clear();
+        if *clock > 1 && self.history.depth() > 0 {
             // set old state
-            self.sim_state = state;
+            self.sim_state = self.history.pop().unwrap();
             let ordered_components = self.ordered_components.clone();
 
             for component in ordered_components {
@@ -212,30 +522,159 @@ impl Simulator {
         *clock = self.history.len();
     }
 
+    /// re-evaluate the combinatorial components for the current clock, e.g.,
+    /// after editing a `ProbeEdit`, without advancing the clock or pushing
+    /// history (sequential outputs are kept)
+    pub fn settle(&mut self) {
+        // the state before the first clock is not evaluated
+        if self.history.is_empty() {
+            return;
+        }
+        self.hits.clear();
+        let ordered_components = self.ordered_components.clone();
+        for (index, component) in ordered_components
+            .iter()
+            .enumerate()
+            .skip(self.nr_sequential)
+        {
+            self.current = index;
+            self.dirty[index] = false;
+            component.settle(self);
+        }
+
+        // breakpoints as if the clock was taken with the settled state
+        if !self.breakpoints.is_empty() {
+            if let Some(prev) = self.history.get(self.history.len() - 1) {
+                self.check_breakpoints(&prev);
+            }
+        }
+    }
+
     /// reset simulator
     pub fn reset(&mut self, clock: &mut usize) {
-        self.history = vec![];
-        self.sim_state.iter_mut().for_each(|val| *val = 0);
+        self.history.clear();
+        self.init_state();
+        for component in &self.ordered_components {
+            component.reset();
+        }
         self.clock(clock);
     }
 
-    /// save as `dot` file with `.gv` extension
-    pub fn save_dot(&self, path: &PathBuf) {
+    /// select four-state (`true`) or two-state signals, resetting the
+    /// simulation
+    pub fn set_four_state(&mut self, four_state: bool, clock: &mut usize) {
+        self.four_state = four_state;
+        self.reset(clock);
+    }
+
+    // the state before the first clock, all outputs are 0, or unknown (X)
+    // in four-state mode, where `sim_state`, `masks` and `fanout` are
+    // extended by the unknown bits of each output
+    pub(crate) fn init_state(&mut self) {
+        let n = self.widths.len();
+        self.masks.truncate(n);
+        self.fanout.truncate(n);
+        if self.four_state {
+            self.masks.extend_from_within(..);
+            self.fanout.extend_from_within(..);
+            self.sim_state = self.masks.clone();
+        } else {
+            self.sim_state = vec![0; n];
+        }
+    }
+
+    /// select full or incremental evaluation, taking effect on the next clock
+    pub fn set_eval_mode(&mut self, eval_mode: EvalMode) {
+        self.eval_mode = eval_mode;
+    }
+
+    /// limit the number of clocks kept in history (`None` for unlimited),
+    /// older clocks can no longer be stepped back to
+    pub fn set_history_depth(&mut self, max_depth: Option<usize>) {
+        let dropped = self.history.dropped();
+        self.history.set_max_depth(max_depth);
+        self.forget_dropped(dropped);
+    }
+
+    /// save as `dot` file with `.gv` extension, edges are labeled by the
+    /// width of the driving output (if known)
+    pub fn save_dot(&self, path: &PathBuf) -> Result<(), SimError> {
         let mut path = path.to_owned();
         path.set_extension("gv");
-        let mut file = File::create(path).unwrap();
+        let edge_label = |_, edge: EdgeReference<Id>| {
+            let from = &self.graph[edge.source()];
+            match self.index(from, edge.weight()).and_then(|i| self.widths[i]) {
+                Some(width) => format!("label = \"{}\"", width),
+                None => String::new(),
+            }
+        };
         let dot_string = format!(
             "{:?}",
-            Dot::with_config(&self.graph, &[Config::EdgeNoLabel])
+            Dot::with_attr_getters(&self.graph, &[Config::EdgeNoLabel], &edge_label, &|_, _| {
+                String::new()
+            })
         );
-        file.write_all(dot_string.as_bytes()).unwrap();
+        File::create(&path)
+            .and_then(|mut file| file.write_all(dot_string.as_bytes()))
+            .map_err(|error| SimError::Io { path, error })
+    }
+}
+
+// Find the combinatorial loops of the dependency graph, one loop for each
+// strongly connected component. Each loop starts at the component with the
+// smallest id and lists the outputs closing the loop in evaluation order.
+fn find_loops(graph: &Graph<Id, Id>) -> Vec<CombinatorialLoop> {
+    let mut loops = vec![];
+    for scc in tarjan_scc(graph) {
+        let start = *scc.iter().min_by_key(|node| &graph[**node]).unwrap();
+        // a single component only forms a loop if it depends on itself
+        if scc.len() == 1 && graph.find_edge(start, start).is_none() {
+            continue;
+        }
+
+        // breadth first search within the scc, until we get back to start
+        let mut parent: HashMap<NodeIndex, EdgeIndex> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        'search: while let Some(node) = queue.pop_front() {
+            for edge in graph.edges(node) {
+                let target = edge.target();
+                if target == start {
+                    parent.insert(start, edge.id());
+                    break 'search;
+                }
+                if scc.contains(&target) && !parent.contains_key(&target) {
+                    parent.insert(target, edge.id());
+                    queue.push_back(target);
+                }
+            }
+        }
+
+        // walk the path backwards from start
+        let mut path = vec![];
+        let mut node = start;
+        loop {
+            let edge = parent[&node];
+            let (from, _) = graph.edge_endpoints(edge).unwrap();
+            path.push(Input::new(&graph[from], &graph[edge]));
+            node = from;
+            if node == start {
+                break;
+            }
+        }
+        path.reverse();
+        trace!("loop {:?}", path);
+        loops.push(CombinatorialLoop(path));
     }
+    loops.sort_by(|a, b| a.0[0].id.cmp(&b.0[0].id));
+    loops
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::common::{component, ComponentPorts};
     use crate::components::*;
+    use serde::{Deserialize, Serialize};
     use std::rc::Rc;
 
     #[test]
@@ -245,22 +684,158 @@ mod test {
         };
 
         let mut clock = 0;
-        let _simulator = Simulator::new(&cs, &mut clock);
+        let _simulator = Simulator::new(&cs, &mut clock).unwrap();
 
         assert_eq!(clock, 1);
     }
 
     #[test]
-    #[should_panic(expected = "Component identifier \"po1\" is defined twice")]
     fn test_redefined() {
         let cs = ComponentStore {
             store: vec![Rc::new(ProbeOut::new("po1")), Rc::new(ProbeOut::new("po1"))],
         };
 
         let mut clock = 0;
-        let _simulator = Simulator::new(&cs, &mut clock);
+        let err = Simulator::new(&cs, &mut clock).err().unwrap();
 
-        assert_eq!(clock, 1);
+        assert!(matches!(err, SimError::DuplicateId(ref id) if id == "po1"));
+        assert_eq!(
+            err.to_string(),
+            "Component identifier \"po1\" is defined twice"
+        );
+    }
+
+    #[test]
+    fn test_unknown_input() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("po1")),
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("po2", "out"),
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let err = Simulator::new(&cs, &mut clock).err().unwrap();
+        assert!(matches!(err, SimError::UnknownComponent { ref id, .. } if id == "reg"));
+
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("po1")),
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("po1", "missing"),
+                }),
+            ],
+        };
+
+        let err = Simulator::new(&cs, &mut clock).err().unwrap();
+        assert!(matches!(err, SimError::UnknownField { ref id, .. } if id == "reg"));
+    }
+
+    #[test]
+    fn test_loop() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(Add {
+                    id: "add1".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("add2", "out"),
+                    b_in: Input::new("add2", "out"),
+                    width: 32,
+                }),
+                Rc::new(Add {
+                    id: "add2".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("add1", "out"),
+                    b_in: Input::new("add1", "out"),
+                    width: 32,
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let err = Simulator::new(&cs, &mut clock).err().unwrap();
+        let SimError::Loop(loops) = err else {
+            panic!("expected loop, got {}", err);
+        };
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].to_string(), "add1.out -> add2.out -> add1");
+
+        // viewed, the model is kept with its loops, but not evaluated
+        let mut simulator = Simulator::view(&cs, &mut clock).unwrap();
+        assert_eq!(simulator.loops, loops);
+        assert_eq!(simulator.ordered_components.len(), 2);
+        assert_eq!(simulator.loop_fields("add2"), vec!["out"]);
+        simulator.clock(&mut clock);
+        assert_eq!(clock, 0);
+    }
+
+    #[test]
+    fn test_loops() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("po1")),
+                // self loop
+                Rc::new(Add {
+                    id: "add1".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("po1", "out"),
+                    b_in: Input::new("add1", "out"),
+                    width: 32,
+                }),
+                // loop over three components, closed by the overflow field
+                Rc::new(Add {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("b", "out"),
+                    b_in: Input::new("po1", "out"),
+                    width: 32,
+                }),
+                Rc::new(Add {
+                    id: "b".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("a", "out"),
+                    b_in: Input::new("po1", "out"),
+                    width: 32,
+                }),
+                Rc::new(Add {
+                    id: "a".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("c", "overflow"),
+                    b_in: Input::new("po1", "out"),
+                    width: 32,
+                }),
+                // a register breaks the loop
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("add2", "out"),
+                }),
+                Rc::new(Add {
+                    id: "add2".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("reg", "out"),
+                    b_in: Input::new("po1", "out"),
+                    width: 32,
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let err = Simulator::new(&cs, &mut clock).err().unwrap();
+        let SimError::Loop(loops) = err else {
+            panic!("expected loop, got {}", err);
+        };
+        let loops: Vec<String> = loops.iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            loops,
+            vec!["a.out -> b.out -> c.overflow -> a", "add1.out -> add1"]
+        );
     }
 
     #[test]
@@ -270,7 +845,7 @@ mod test {
         };
 
         let mut clock = 0;
-        let simulator = Simulator::new(&cs, &mut clock);
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
 
         assert_eq!(clock, 1);
         let _ = simulator.get_input_val(&Input::new("po1", "out"));
@@ -284,9 +859,446 @@ mod test {
         };
 
         let mut clock = 0;
-        let simulator = Simulator::new(&cs, &mut clock);
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
 
         assert_eq!(clock, 1);
         let _ = simulator.get_input_val(&Input::new("po1", "missing"));
     }
+
+    #[test]
+    fn test_input_index() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(Add {
+                    id: "add".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("c", "out"),
+                    b_in: Input::new("add2", "overflow"),
+                    width: 32,
+                }),
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    value: 7,
+                }),
+                Rc::new(Add {
+                    id: "add2".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("c", "out"),
+                    b_in: Input::new("c", "out"),
+                    width: 32,
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let simulator = Simulator::new(&cs, &mut clock).unwrap();
+        // outputs are allocated in store order: add (0, 1), c (2), add2 (3, 4)
+        let add = simulator.component_ids.iter().position(|id| id == "add");
+        let add = add.unwrap();
+        assert_eq!(simulator.output_index[add], 0);
+        assert_eq!(simulator.input_index[add], vec![2, 4]);
+        assert_eq!(simulator.get_input_val(&Input::new("add", "out")), 7);
+        assert_eq!(simulator.get_input_val(&Input::new("add2", "out")), 14);
+    }
+
+    #[test]
+    fn test_derived_ports() {
+        let mux = Mux {
+            id: "mux".into(),
+            pos: (0.0, 0.0),
+            select: Input::new("c", "out"),
+            m_in: vec![Input::new("a", "out"), Input::new("b", "out")],
+        };
+        let (id, ports) = mux.get_id_ports();
+        assert_eq!(id, "mux");
+        assert_eq!(
+            ports.inputs,
+            vec![
+                Input::new("c", "out"),
+                Input::new("a", "out"),
+                Input::new("b", "out")
+            ]
+        );
+        assert_eq!(ports.outputs, vec!["out"]);
+        assert_eq!(mux.out(), Input::new("mux", "out"));
+
+        let reg = Register {
+            id: "reg".into(),
+            pos: (0.0, 0.0),
+            r_in: mux.out(),
+        };
+        assert!(matches!(
+            reg.get_id_ports().1.out_type,
+            OutputType::Sequential
+        ));
+    }
+
+    // a component with a 1 bit input
+    #[derive(Serialize, Deserialize, ComponentPorts)]
+    #[outputs(out)]
+    struct Bit {
+        id: Id,
+        #[input(width = 1)]
+        bit: Input,
+    }
+
+    #[component]
+    #[typetag::serde]
+    impl Component for Bit {}
+
+    #[cfg(feature = "gui-vizia")]
+    #[typetag::serde]
+    impl crate::common::ViziaComponent for Bit {}
+
+    #[cfg(feature = "gui-egui")]
+    #[typetag::serde]
+    impl crate::common::EguiComponent for Bit {}
+
+    #[test]
+    fn test_width() {
+        let sext = |id: &str, input: &str, in_size, out_size| Sext {
+            id: id.into(),
+            pos: (0.0, 0.0),
+            sext_in: Input::new(input, "out"),
+            in_size,
+            out_size,
+        };
+        let mut cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("po")),
+                Rc::new(sext("s1", "po", 4, 8)),
+                Rc::new(sext("s2", "s1", 8, 16)),
+            ],
+        };
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        assert_eq!(simulator.widths, vec![None, Some(8), Some(16)]);
+        // outputs are masked to their width
+        simulator.set_out_val("s1", "out", 0x1234);
+        assert_eq!(simulator.get_input_val(&Input::new("s1", "out")), 0x34);
+        simulator.set_out_val("po", "out", 0xf);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(&Input::new("s2", "out")), 0xffff);
+
+        // an 8 bit output driving a 1 bit input
+        cs.store.push(Rc::new(Bit {
+            id: "bit".into(),
+            bit: Input::new("s1", "out"),
+        }));
+        assert!(matches!(
+            Simulator::new(&cs, &mut clock),
+            Err(SimError::Width {
+                width: 8,
+                input_width: 1,
+                ..
+            })
+        ));
+
+        // an output width outside 1 ..= 64
+        cs.store.pop();
+        cs.store.push(Rc::new(Add {
+            id: "add".into(),
+            pos: (0.0, 0.0),
+            a_in: Input::new("po", "out"),
+            b_in: Input::new("po", "out"),
+            width: 0,
+        }));
+        assert!(matches!(
+            Simulator::new(&cs, &mut clock),
+            Err(SimError::OutputWidth { width: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_incremental() {
+        // counter, holding while sel.out = 0, counting by inc.out otherwise
+        fn model(sel: &Rc<ProbeEdit>, inc: &Rc<ProbeEdit>) -> ComponentStore {
+            ComponentStore {
+                store: vec![
+                    sel.clone(),
+                    inc.clone(),
+                    Rc::new(Register {
+                        id: "reg".into(),
+                        pos: (0.0, 0.0),
+                        r_in: Input::new("sext", "out"),
+                    }),
+                    Rc::new(Add {
+                        id: "add".into(),
+                        pos: (0.0, 0.0),
+                        a_in: Input::new("reg", "out"),
+                        b_in: Input::new("inc", "out"),
+                        width: 32,
+                    }),
+                    Rc::new(Mux {
+                        id: "mux".into(),
+                        pos: (0.0, 0.0),
+                        select: Input::new("sel", "out"),
+                        m_in: vec![Input::new("reg", "out"), Input::new("add", "out")],
+                    }),
+                    Rc::new(Sext {
+                        id: "sext".into(),
+                        pos: (0.0, 0.0),
+                        sext_in: Input::new("mux", "out"),
+                        in_size: 4,
+                        out_size: 32,
+                    }),
+                ],
+            }
+        }
+        let edit = |probe: &Rc<ProbeEdit>, value| {
+            probe.history.write().unwrap().last_mut().unwrap().signal = value;
+        };
+
+        let probes: Vec<_> = (0..4)
+            .map(|i| Rc::new(ProbeEdit::new(["sel", "inc"][i % 2], (0.0, 0.0))))
+            .collect();
+        let mut clock = 0;
+        let mut full = Simulator::new(&model(&probes[0], &probes[1]), &mut clock).unwrap();
+        let mut clock_inc = 0;
+        let mut inc = Simulator::new(&model(&probes[2], &probes[3]), &mut clock_inc).unwrap();
+        inc.set_eval_mode(EvalMode::Incremental);
+
+        for n in 0..40 {
+            match n {
+                8 => [0, 2].map(|i| edit(&probes[i], 1)),
+                9 => [1, 3].map(|i| edit(&probes[i], 3)),
+                30 => [0, 2].map(|i| edit(&probes[i], 0)),
+                _ => [(); 2],
+            };
+            if n % 7 == 6 {
+                full.un_clock(&mut clock);
+                inc.un_clock(&mut clock_inc);
+            } else {
+                full.clock(&mut clock);
+                inc.clock(&mut clock_inc);
+            }
+            assert_eq!(clock, clock_inc);
+            assert_eq!(full.sim_state, inc.sim_state, "clock {}", clock);
+        }
+        // the counter wrapped around (4 bit sign extended)
+        assert_eq!(inc.get_input_val(&Input::new("reg", "out")), 0xffff_fffd);
+
+        full.reset(&mut clock);
+        inc.reset(&mut clock_inc);
+        assert_eq!(full.sim_state, inc.sim_state);
+    }
+
+    #[test]
+    fn test_settle() {
+        // counter, counting by inc.out
+        let inc = Rc::new(ProbeEdit::new("inc", (0.0, 0.0)));
+        let cs = ComponentStore {
+            store: vec![
+                inc.clone(),
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("add", "out"),
+                }),
+                Rc::new(Add {
+                    id: "add".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("reg", "out"),
+                    b_in: Input::new("inc", "out"),
+                    width: 32,
+                }),
+            ],
+        };
+        let (reg, add) = (Input::new("reg", "out"), Input::new("add", "out"));
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_eval_mode(EvalMode::Incremental);
+        inc.history.write().unwrap().last_mut().unwrap().signal = 2;
+        simulator.clock(&mut clock);
+        simulator.clock(&mut clock);
+        assert_eq!(clock, 3);
+        assert_eq!(simulator.get_input_val(&reg), 2);
+        assert_eq!(simulator.get_input_val(&add), 4);
+
+        // the edit takes effect on the current clock
+        inc.history.write().unwrap().last_mut().unwrap().signal = 5;
+        simulator.settle();
+        assert_eq!(clock, 3);
+        assert_eq!(simulator.history.len(), 3);
+        assert_eq!(simulator.get_input_val(&reg), 2);
+        assert_eq!(simulator.get_input_val(&add), 7);
+
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(&reg), 7);
+        assert_eq!(simulator.get_input_val(&add), 12);
+
+        // stepping back restores the state before the clock
+        simulator.un_clock(&mut clock);
+        simulator.un_clock(&mut clock);
+        assert_eq!(clock, 2);
+        assert_eq!(simulator.get_input_val(&add), 2);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.get_input_val(&add), 4);
+    }
+
+    #[test]
+    fn test_history_depth() {
+        // counter, reg.out = add.out delayed one clock
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    value: 1,
+                }),
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("add", "out"),
+                }),
+                Rc::new(Add {
+                    id: "add".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("c", "out"),
+                    b_in: Input::new("reg", "out"),
+                    width: 32,
+                }),
+            ],
+        };
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_history_depth(Some(3));
+        for _ in 0..1000 {
+            simulator.clock(&mut clock);
+        }
+        assert_eq!(clock, 1001);
+        assert_eq!(simulator.history.depth(), 3);
+
+        let reg = Input::new("reg", "out");
+        assert_eq!(simulator.get_input_val(&reg), 1000);
+        for _ in 0..5 {
+            simulator.un_clock(&mut clock);
+        }
+        // only three clocks could be stepped back
+        assert_eq!(clock, 998);
+        assert_eq!(simulator.get_input_val(&reg), 997);
+
+        simulator.clock(&mut clock);
+        assert_eq!(clock, 999);
+        assert_eq!(simulator.get_input_val(&reg), 998);
+
+        simulator.reset(&mut clock);
+        assert_eq!(clock, 1);
+        assert_eq!(simulator.get_input_val(&reg), 0);
+    }
+
+    #[test]
+    fn test_four_state_bits() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("a")),
+                Rc::new(ProbeOut::new("b")),
+                Rc::new(Add {
+                    id: "add".into(),
+                    pos: (0.0, 0.0),
+                    a_in: Input::new("a", "out"),
+                    b_in: Input::new("b", "out"),
+                    width: 8,
+                }),
+                Rc::new(Sext {
+                    id: "sext".into(),
+                    pos: (0.0, 0.0),
+                    sext_in: Input::new("a", "out"),
+                    in_size: 4,
+                    out_size: 8,
+                }),
+            ],
+        };
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_four_state(true, &mut clock);
+        let xz = |simulator: &Simulator, id: &str, field: &str| {
+            simulator.get_xz(simulator.index(id, field).unwrap())
+        };
+
+        // bit 4 of a unknown, the sum from bit 4 up, the low 4 bits are
+        // extended as known
+        let a = simulator.index("a", "out").unwrap();
+        simulator.set_xz(a, 0x11, 0x10);
+        simulator.set_out_val("b", "out", 0x02);
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(&Input::new("add", "out")), "0xX3");
+        assert_eq!(xz(&simulator, "add", "out"), 0xf0);
+        assert_eq!(xz(&simulator, "add", "overflow"), 1);
+        assert_eq!(simulator.format_input(&Input::new("sext", "out")), "1");
+
+        // bit 3 (the sign) of a unknown, also the extended bits
+        simulator.set_xz(a, 0x09, 0x08);
+        simulator.clock(&mut clock);
+        assert_eq!(xz(&simulator, "add", "out"), 0xf8);
+        assert_eq!(xz(&simulator, "sext", "out"), 0xf8);
+        assert_eq!(simulator.get_input_val(&Input::new("sext", "out")), 0xf9);
+    }
+
+    #[test]
+    fn test_four_state() {
+        assert_eq!(format_signal(0x12, 0, Some(8)), "18");
+        assert_eq!(format_signal(0xff, 0xff, Some(8)), "X");
+        assert_eq!(format_signal(0x00, 0xff, Some(8)), "Z");
+        assert_eq!(format_signal(0x1f, 0xf0, Some(8)), "0xXf");
+        assert_eq!(format_signal(0x0f, 0xf0, Some(8)), "0xZf");
+        assert_eq!(format_signal(0x00, 0x01, Some(5)), "0x0Z");
+
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(ProbeOut::new("sel")),
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    value: 1,
+                }),
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("c", "out"),
+                }),
+                Rc::new(Mux {
+                    id: "mux".into(),
+                    pos: (0.0, 0.0),
+                    select: Input::new("sel", "out"),
+                    m_in: vec![Input::new("reg", "out"), Input::new("c", "out")],
+                }),
+            ],
+        };
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.set_four_state(true, &mut clock);
+        assert_eq!(simulator.sim_state.len(), 2 * simulator.widths.len());
+
+        // the register samples the unknown reset state, the select is unknown
+        let reg = Input::new("reg", "out");
+        let mux = Input::new("mux", "out");
+        assert_eq!(clock, 1);
+        assert_eq!(simulator.format_input(&reg), "X");
+        assert_eq!(simulator.format_input(&mux), "X");
+
+        // a known select passes the selected input, known or not
+        simulator.set_out_val("sel", "out", 0);
+        simulator.settle();
+        assert_eq!(simulator.format_input(&mux), "X");
+        simulator.clock(&mut clock);
+        assert_eq!(simulator.format_input(&reg), "1");
+        assert_eq!(simulator.format_input(&mux), "1");
+
+        // the unknown bits are part of the history and checkpoints
+        let checkpoint = simulator.checkpoint();
+        simulator.un_clock(&mut clock);
+        assert_eq!(simulator.format_input(&reg), "X");
+        let restored = Simulator::restore(&checkpoint, &mut clock).unwrap();
+        assert!(restored.four_state);
+        assert_eq!(restored.format_input(&reg), "1");
+
+        // back to two-state, all outputs start as 0
+        simulator.set_four_state(false, &mut clock);
+        assert_eq!(simulator.sim_state.len(), simulator.widths.len());
+        assert_eq!(simulator.format_input(&mux), "0");
+    }
 }
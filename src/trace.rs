@@ -0,0 +1,327 @@
+// Reference traces (VCD or CSV) for golden-trace comparison
+use crate::common::{parse_signal, Input, Signal, SimError, Simulator};
+use std::{collections::HashMap, fmt, fs, path::PathBuf};
+
+// A reference trace, holding the expected value of each signal per clock
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub signals: Vec<Input>,
+    // (clock, values), `None` where the trace has no known value
+    pub cycles: Vec<(usize, Vec<Option<Signal>>)>,
+}
+
+// The first difference found between a trace and a simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub clock: usize,
+    pub signal: Input,
+    pub expected: Signal,
+    pub actual: Signal,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Mismatch at clock {}, {} expected {} (0x{:x}), actual {} (0x{:x})",
+            self.clock, self.signal, self.expected, self.expected, self.actual, self.actual
+        )
+    }
+}
+
+fn parse_err(line: usize, msg: impl fmt::Display) -> SimError {
+    SimError::Parse {
+        path: format!("line {}", line + 1),
+        msg: msg.to_string(),
+    }
+}
+
+impl Trace {
+    /// load a trace, the format (`vcd` or `csv`) is given by the extension,
+    /// for VCD files `period` is the time of one clock cycle
+    pub fn load_file(path: &PathBuf, period: u64) -> Result<Self, SimError> {
+        let text = fs::read_to_string(path).map_err(|error| SimError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("vcd") => Trace::from_vcd(&text, period),
+            Some("csv") => Trace::from_csv(&text),
+            _ => Err(SimError::Parse {
+                path: path.display().to_string(),
+                msg: "unknown trace format, expected `.vcd` or `.csv`".into(),
+            }),
+        }
+    }
+
+    /// parse a CSV trace, with a header `cycle,id.field,...` and one row per
+    /// clock, values in decimal or hex, empty values are not compared
+    pub fn from_csv(text: &str) -> Result<Self, SimError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let (_, header) = lines.next().ok_or_else(|| parse_err(0, "empty trace"))?;
+        let mut columns = header.split(',');
+        if columns.next().map(|c| c.trim()) != Some("cycle") {
+            return Err(parse_err(0, "first column should be `cycle`"));
+        }
+        let signals = columns
+            .map(|c| c.parse())
+            .collect::<Result<Vec<Input>, _>>()
+            .map_err(|e| parse_err(0, e))?;
+
+        let mut cycles = vec![];
+        for (line, row) in lines {
+            let mut values = row.split(',');
+            let clock = values
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .parse()
+                .map_err(|e| parse_err(line, e))?;
+            let values = values
+                .map(|v| match v.trim() {
+                    "" => Ok(None),
+                    v => parse_signal(v).map(Some),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| parse_err(line, e))?;
+            if values.len() != signals.len() {
+                return Err(parse_err(line, "number of values differ from header"));
+            }
+            cycles.push((clock, values));
+        }
+        Ok(Trace { signals, cycles })
+    }
+
+    /// parse a VCD trace, a variable `field` in scope `id` gives the signal
+    /// `id.field`, the value at clock `n` is the value at time `n * period`
+    pub fn from_vcd(text: &str, period: u64) -> Result<Self, SimError> {
+        let period = period.max(1);
+        let mut tokens = text
+            .lines()
+            .enumerate()
+            .flat_map(|(line, l)| l.split_whitespace().map(move |t| (line, t)));
+        let mut next = |line| {
+            tokens
+                .next()
+                .ok_or_else(|| parse_err(line, "unexpected end"))
+        };
+
+        // declarations
+        let mut signals = vec![];
+        // identifier code -> signal indexes (a code may be shared)
+        let mut codes: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut scopes = vec![];
+        let mut line = 0;
+        loop {
+            let (l, token) = next(line)?;
+            line = l;
+            match token {
+                "$scope" => {
+                    let _kind = next(line)?;
+                    scopes.push(next(line)?.1);
+                }
+                "$upscope" => {
+                    scopes.pop();
+                }
+                "$var" => {
+                    let _kind = next(line)?;
+                    let _width = next(line)?;
+                    let code = next(line)?.1;
+                    let field = next(line)?.1;
+                    let id = scopes
+                        .last()
+                        .ok_or_else(|| parse_err(line, "variable outside of scope"))?;
+                    codes.entry(code).or_default().push(signals.len());
+                    signals.push(Input::new(id, field));
+                }
+                "$enddefinitions" => break,
+                _ => {}
+            }
+            // skip to end of declaration
+            if token.starts_with('$') && token != "$end" {
+                while next(line)?.1 != "$end" {}
+            }
+        }
+
+        // value changes
+        let mut values = vec![None; signals.len()];
+        let mut cycles: Vec<(usize, Vec<Option<Signal>>)> = vec![];
+        let mut clock = None;
+        let mut record = |clock, values: &Vec<Option<Signal>>| match cycles.last_mut() {
+            Some((last, last_values)) if *last == clock => *last_values = values.clone(),
+            _ => cycles.push((clock, values.clone())),
+        };
+        while let Ok((line, token)) = next(line) {
+            let (value, code) = match token.as_bytes()[0] {
+                b'#' => {
+                    let time: u64 = token[1..].parse().map_err(|e| parse_err(line, e))?;
+                    if let Some(clock) = clock {
+                        record(clock, &values);
+                    }
+                    clock = Some((time / period) as usize);
+                    continue;
+                }
+                // $dumpvars, $end, etc.
+                b'$' => continue,
+                b'0' | b'1' | b'x' | b'X' | b'z' | b'Z' => (&token[..1], &token[1..]),
+                b'b' | b'B' => (&token[1..], next(line)?.1),
+                // real values are not compared
+                b'r' | b'R' => ("x", next(line)?.1),
+                _ => return Err(parse_err(line, format!("unexpected {:?}", token))),
+            };
+            let value = if value.contains(['x', 'X', 'z', 'Z']) {
+                None
+            } else {
+                Some(Signal::from_str_radix(value, 2).map_err(|e| parse_err(line, e))?)
+            };
+            for index in codes.get(code).into_iter().flatten() {
+                values[*index] = value;
+            }
+        }
+        if let Some(clock) = clock {
+            record(clock, &values);
+        }
+        Ok(Trace { signals, cycles })
+    }
+}
+
+impl Simulator {
+    /// clock the simulator along `trace`, comparing each traced signal
+    /// every cycle, returns the first mismatch (if any)
+    pub fn compare_trace(
+        &mut self,
+        trace: &Trace,
+        clock: &mut usize,
+    ) -> Result<Option<Mismatch>, SimError> {
+        let indexes = trace
+            .signals
+            .iter()
+            .map(|signal| self.get_input_index(signal))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (cycle, values) in &trace.cycles {
+            // skip cycles before the current clock (e.g., before reset)
+            if *cycle < *clock {
+                continue;
+            }
+            while *clock < *cycle {
+                self.clock(clock);
+            }
+            for ((signal, index), expected) in trace.signals.iter().zip(&indexes).zip(values) {
+                match expected {
+                    Some(expected) if *expected != self.sim_state[*index] => {
+                        return Ok(Some(Mismatch {
+                            clock: *clock,
+                            signal: signal.clone(),
+                            expected: *expected,
+                            actual: self.sim_state[*index],
+                        }))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::counter;
+
+    #[test]
+    fn test_csv() {
+        let trace = Trace::from_csv("cycle,add.out,reg.out\n1,1,0\n2,2,\n3,0x3,2\n").unwrap();
+        assert_eq!(
+            trace.signals,
+            vec![Input::new("add", "out"), Input::new("reg", "out")]
+        );
+        assert_eq!(trace.cycles[1], (2, vec![Some(2), None]));
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        assert_eq!(simulator.compare_trace(&trace, &mut clock).unwrap(), None);
+        assert_eq!(clock, 3);
+
+        let trace = Trace::from_csv("cycle,reg.out\n1,0\n4,2\n5,4").unwrap();
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        let mismatch = simulator
+            .compare_trace(&trace, &mut clock)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            mismatch,
+            Mismatch {
+                clock: 4,
+                signal: Input::new("reg", "out"),
+                expected: 2,
+                actual: 3
+            }
+        );
+        assert_eq!(clock, 4);
+    }
+
+    #[test]
+    fn test_vcd() {
+        // trace of a known good run
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        for _ in 0..5 {
+            simulator.clock(&mut clock);
+        }
+        let mut vcd = vec![];
+        simulator.write_vcd(&mut vcd).unwrap();
+        let trace = Trace::from_vcd(&String::from_utf8(vcd).unwrap(), 1).unwrap();
+        assert_eq!(trace.cycles.len(), 6);
+        assert_eq!(trace.cycles[5].0, 6);
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        assert_eq!(simulator.compare_trace(&trace, &mut clock).unwrap(), None);
+        assert_eq!(clock, 6);
+    }
+
+    #[test]
+    fn test_vcd_rtl() {
+        // trace with a 10ns clock period, unknown values before reset
+        let vcd = "$timescale 1ns $end
+$scope module tb $end
+$scope module reg $end
+$var reg 32 # out [31:0] $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+bx #
+$end
+#10
+b0 #
+#20
+b1 #
+#30
+#40
+b11 #
+";
+        let trace = Trace::from_vcd(vcd, 10).unwrap();
+        assert_eq!(trace.cycles[0], (0, vec![None]));
+        assert_eq!(trace.cycles[3], (3, vec![Some(1)]));
+
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&counter(1), &mut clock).unwrap();
+        let mismatch = simulator
+            .compare_trace(&trace, &mut clock)
+            .unwrap()
+            .unwrap();
+        assert_eq!(mismatch.clock, 3);
+        assert_eq!(
+            mismatch.to_string(),
+            "Mismatch at clock 3, reg.out expected 1 (0x1), actual 2 (0x2)"
+        );
+    }
+}
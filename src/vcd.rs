@@ -0,0 +1,199 @@
+// Value Change Dump (VCD) export of the simulation history
+use crate::common::{Signal, SimError, Simulator};
+use std::{fs::File, io::prelude::*, io::BufWriter, path::PathBuf};
+
+// VCD identifier code for variable `index`, printable ASCII from `!` to `~`
+fn code(mut index: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
+
+// write `value` with unknown bits `xz`, as `x` (value bit 1) or `z` (0)
+fn write_value(
+    out: &mut impl Write,
+    width: u32,
+    (value, xz): (Signal, Signal),
+    code: &str,
+) -> std::io::Result<()> {
+    let bit = |i: u32| match ((value >> i) & 1, (xz >> i) & 1) {
+        (value, 0) => char::from_digit(value as u32, 2).unwrap(),
+        (1, _) => 'x',
+        _ => 'z',
+    };
+    if width == 1 {
+        writeln!(out, "{}{}", bit(0), code)
+    } else if xz == 0 {
+        writeln!(out, "b{:b} {}", value, code)
+    } else {
+        let bits: String = (0..width).rev().map(bit).collect();
+        writeln!(out, "b{} {}", bits, code)
+    }
+}
+
+impl Simulator {
+    /// the (clock, state) of each clock cycle kept in history, starting
+    /// from the reset state (clock 1) unless older clocks have been dropped
+    pub fn states(&self) -> impl Iterator<Item = (usize, Vec<Signal>)> + '_ {
+        // history entry n is the state before clock n + 1 was evaluated
+        (self.history.dropped()..)
+            .zip(self.history.iter())
+            .filter(|(clock, _)| *clock > 0)
+            .chain(std::iter::once((
+                self.history.len(),
+                self.sim_state.clone(),
+            )))
+    }
+
+    /// write the simulation history as a VCD, one scope per component
+    /// and one variable per output, time is given by the clock number
+    pub fn write_vcd(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "$version SyncRim {} $end", env!("CARGO_PKG_VERSION"))?;
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module top $end")?;
+        let mut vars = vec![];
+        for c in &self.ordered_components {
+            let (id, ports) = c.get_id_ports();
+            if ports.outputs.is_empty() {
+                continue;
+            }
+            writeln!(out, "$scope module {} $end", id)?;
+            let start_index = self.get_id_start_index(&id);
+            for (i, field) in ports.outputs.iter().enumerate() {
+                let width = self.widths[start_index + i].unwrap_or(Signal::BITS);
+                let code = code(vars.len());
+                writeln!(out, "$var wire {} {} {} $end", width, code, field)?;
+                vars.push((start_index + i, width, code));
+            }
+            writeln!(out, "$upscope $end")?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+
+        // the (value, unknown bits) of `index`, see `get_xz`
+        let n = self.widths.len();
+        let signal = |state: &[Signal], index: usize| {
+            (state[index], state.get(n + index).copied().unwrap_or(0))
+        };
+        let mut prev: Option<Vec<Signal>> = None;
+        for (clock, state) in self.states() {
+            writeln!(out, "#{}", clock)?;
+            match prev {
+                None => {
+                    writeln!(out, "$dumpvars")?;
+                    for (index, width, code) in &vars {
+                        write_value(out, *width, signal(&state, *index), code)?;
+                    }
+                    writeln!(out, "$end")?;
+                }
+                Some(ref prev) => {
+                    for (index, width, code) in &vars {
+                        if signal(prev, *index) != signal(&state, *index) {
+                            write_value(out, *width, signal(&state, *index), code)?;
+                        }
+                    }
+                }
+            }
+            prev = Some(state);
+        }
+        Ok(())
+    }
+
+    /// save the simulation history as a VCD file with `.vcd` extension
+    pub fn save_vcd(&self, path: &PathBuf) -> Result<(), SimError> {
+        let mut path = path.to_owned();
+        path.set_extension("vcd");
+        File::create(&path)
+            .and_then(|file| {
+                let mut out = BufWriter::new(file);
+                self.write_vcd(&mut out)?;
+                out.flush()
+            })
+            .map_err(|error| SimError::Io { path, error })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common::{ComponentStore, Input};
+    use crate::components::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_code() {
+        assert_eq!(code(0), "!");
+        assert_eq!(code(93), "~");
+        assert_eq!(code(94), "!!");
+        assert_eq!(code(95), "\"!");
+    }
+
+    #[test]
+    fn test_write_value() {
+        let mut out = vec![];
+        write_value(&mut out, 4, (0b1010, 0), "!").unwrap();
+        write_value(&mut out, 4, (0b1010, 0b0110), "!").unwrap();
+        write_value(&mut out, 1, (1, 1), "\"").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "b1010 !\nb1zx0 !\nx\"\n");
+    }
+
+    #[test]
+    fn test_vcd() {
+        let cs = ComponentStore {
+            store: vec![
+                Rc::new(Constant {
+                    id: "c".into(),
+                    pos: (0.0, 0.0),
+                    value: 1,
+                }),
+                Rc::new(Register {
+                    id: "reg".into(),
+                    pos: (0.0, 0.0),
+                    r_in: Input::new("c", "out"),
+                }),
+                Rc::new(Probe {
+                    id: "p".into(),
+                    pos: (0.0, 0.0),
+                    input: Input::new("reg", "out"),
+                }),
+            ],
+        };
+        let mut clock = 0;
+        let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+        simulator.clock(&mut clock);
+        simulator.clock(&mut clock);
+
+        let mut out = vec![];
+        simulator.write_vcd(&mut out).unwrap();
+        let vcd = String::from_utf8(out).unwrap();
+        let body = vcd.split_once("$timescale").unwrap().1;
+        assert_eq!(
+            body,
+            " 1ns $end
+$scope module top $end
+$scope module reg $end
+$var wire 64 ! out $end
+$upscope $end
+$scope module c $end
+$var wire 64 \" out $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#1
+$dumpvars
+b0 !
+b1 \"
+$end
+#2
+b1 !
+#3
+"
+        );
+    }
+}
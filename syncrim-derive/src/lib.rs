@@ -0,0 +1,307 @@
+// Derive macro for the ports of SyncRim components
+//
+// ```ignore
+// #[derive(Serialize, Deserialize, JsonSchema, ComponentSchema, ComponentPorts)]
+// #[outputs(out, overflow(width = 1))]
+// pub struct Add {
+//     pub id: Id,
+//     pub pos: (f32, f32),
+//     #[input]
+//     pub a_in: Input,
+//     #[input]
+//     pub b_in: Input,
+// }
+// ```
+//
+// implements `syncrim::common::ComponentPorts`, giving the ports returned by
+// `Component::get_id_ports` (see `#[component]` below). The inputs are the fields marked `#[input]`
+// (an `Input`) and `#[inputs]` (a `Vec<Input>`), in declaration order. The
+// outputs are given by `#[outputs(..)]`, the component is combinatorial
+// unless marked `#[sequential]`. The struct must have an `id: Id` field.
+//
+// Inputs and outputs take an optional bit width, `width = <expr>`, where the
+// expression may refer to `self` (e.g., `#[input(width = self.in_size)]`).
+// Without a width, any width (up to `Signal::BITS`) is accepted.
+//
+// For each output `out`, the accessors `out(&self) -> Input` (the output as
+// an input of other components), `set_out(simulator, value)` (setting the
+// output of the component being evaluated) and `set_out_xz(simulator, value,
+// xz)` (setting the output with unknown bits, in four-state mode) are
+// generated.
+//
+// A field `pos: (f32, f32)` is returned by `ComponentPorts::pos`.
+//
+// `#[derive(ComponentSchema)]` registers the schema of the component
+// (`syncrim::component_schema!`, see `syncrim::schema`), the struct must also
+// derive `JsonSchema`.
+//
+// The `#[component]` attribute on the `Component` impl implements
+// `get_id_ports` and `pos` from `ComponentPorts`, so that a component only
+// implements its behavior. It also registers the name of the component
+// (`syncrim::schema::ComponentName`), to find components without a schema:
+//
+// ```ignore
+// #[component]
+// #[typetag::serde]
+// impl Component for Add {
+//     fn clock(&self, simulator: &mut Simulator) { .. }
+// }
+// ```
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error,
+    Expr, Fields, Ident, ImplItem, ItemImpl, Meta, Type,
+};
+
+#[proc_macro_derive(ComponentPorts, attributes(input, inputs, outputs, sequential))]
+pub fn derive_component_ports(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+// Registers the schema of the component, generic structs are not components
+// (`typetag` requires concrete types)
+#[proc_macro_derive(ComponentSchema)]
+pub fn derive_component_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Error::new_spanned(&input.generics, "ComponentSchema requires a concrete type")
+            .into_compile_error()
+            .into();
+    }
+    quote!(::syncrim::component_schema!(#name);).into()
+}
+
+// Implements the `Component` methods given by the derived `ComponentPorts`
+// (`get_id_ports` and `pos`), unless implemented by hand, and registers the
+// name of the component. Must precede `#[typetag::serde]`.
+#[proc_macro_attribute]
+pub fn component(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new_spanned(TokenStream2::from(attr), "expected `#[component]`")
+            .into_compile_error()
+            .into();
+    }
+    let mut item = parse_macro_input!(item as ItemImpl);
+    let implemented = |item: &ItemImpl, name: &str| {
+        item.items
+            .iter()
+            .any(|i| matches!(i, ImplItem::Fn(f) if f.sig.ident == name))
+    };
+    if !implemented(&item, "get_id_ports") {
+        item.items.push(parse_quote! {
+            fn get_id_ports(&self) -> (::syncrim::common::Id, ::syncrim::common::Ports) {
+                ::syncrim::common::ComponentPorts::id_ports(self)
+            }
+        });
+    }
+    if !implemented(&item, "pos") {
+        item.items.push(parse_quote! {
+            fn pos(&self) -> ::std::option::Option<(f32, f32)> {
+                ::syncrim::common::ComponentPorts::pos(self)
+            }
+        });
+    }
+    // the `type` tag of `typetag`, the name of the type
+    let Type::Path(ty) = &*item.self_ty else {
+        return Error::new_spanned(&item.self_ty, "expected the component type")
+            .into_compile_error()
+            .into();
+    };
+    let name = ty
+        .path
+        .segments
+        .last()
+        .map(|s| s.ident.to_string())
+        .unwrap_or_default();
+    quote! {
+        #item
+
+        ::syncrim::inventory::submit! {
+            ::syncrim::schema::ComponentName(#name)
+        }
+    }
+    .into()
+}
+
+// parse `width = <expr>` as an `Option<u32>` expression
+fn parse_width(meta: &ParseNestedMeta, width: &mut TokenStream2) -> Result<(), Error> {
+    if meta.path.is_ident("width") {
+        let expr: Expr = meta.value()?.parse()?;
+        *width = quote!(::std::option::Option::Some(#expr));
+        Ok(())
+    } else {
+        Err(meta.error("expected `width = <expr>`"))
+    }
+}
+
+// the width of an `#[input]` or `#[inputs]` attribute
+fn attr_width(attr: &Attribute) -> Result<TokenStream2, Error> {
+    let mut width = quote!(::std::option::Option::None);
+    if let Meta::List(_) = attr.meta {
+        attr.parse_nested_meta(|meta| parse_width(&meta, &mut width))?;
+    }
+    Ok(width)
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(name, "ComponentPorts requires a struct"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            name,
+            "ComponentPorts requires named fields",
+        ));
+    };
+    if !fields
+        .named
+        .iter()
+        .any(|f| f.ident.as_ref().is_some_and(|i| i == "id"))
+    {
+        return Err(Error::new_spanned(
+            name,
+            "ComponentPorts requires an `id` field",
+        ));
+    }
+
+    let has_pos = fields
+        .named
+        .iter()
+        .any(|f| f.ident.as_ref().is_some_and(|i| i == "pos"));
+
+    // inputs and their widths, as vectors concatenated in field order
+    let mut inputs = vec![];
+    let mut input_widths = vec![];
+    for field in &fields.named {
+        let ident = &field.ident;
+        for attr in &field.attrs {
+            if attr.path().is_ident("input") {
+                let width = attr_width(attr)?;
+                inputs.push(quote!(::std::vec![self.#ident.clone()]));
+                input_widths.push(quote!(::std::vec![#width]));
+            } else if attr.path().is_ident("inputs") {
+                let width = attr_width(attr)?;
+                inputs.push(quote!(self.#ident.clone()));
+                input_widths.push(quote!(::std::vec![#width; self.#ident.len()]));
+            }
+        }
+    }
+    let (inputs, input_widths) = if inputs.is_empty() {
+        (
+            quote!(::std::vec::Vec::new()),
+            quote!(::std::vec::Vec::new()),
+        )
+    } else {
+        (
+            quote!([#(#inputs),*].concat()),
+            quote!([#(#input_widths),*].concat()),
+        )
+    };
+
+    let mut outputs: Vec<Ident> = vec![];
+    let mut output_widths = vec![];
+    let mut sequential = false;
+    for attr in &input.attrs {
+        if attr.path().is_ident("outputs") {
+            attr.parse_nested_meta(|meta| {
+                outputs.push(meta.path.require_ident()?.clone());
+                let mut width = quote!(::std::option::Option::None);
+                if meta.input.peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|meta| parse_width(&meta, &mut width))?;
+                }
+                output_widths.push(width);
+                Ok(())
+            })?;
+        } else if attr.path().is_ident("sequential") {
+            sequential = true;
+        }
+    }
+    let out_type = if sequential {
+        quote!(::syncrim::common::OutputType::Sequential)
+    } else {
+        quote!(::syncrim::common::OutputType::Combinatorial)
+    };
+    let fields: Vec<String> = outputs.iter().map(|o| o.to_string()).collect();
+
+    // accessors
+    let accessors = outputs
+        .iter()
+        .zip(&fields)
+        .enumerate()
+        .map(|(index, (output, field))| {
+            let set = format_ident!("set_{}", output);
+            let set_xz = format_ident!("set_{}_xz", output);
+            let doc = format!("output `{}`, as an input of other components", field);
+            let set_doc = format!(
+                "set output `{}` of the component being evaluated (in `clock`)",
+                field
+            );
+            let set_xz_doc = format!(
+                "set output `{}` with unknown (X/Z) bits `xz`, see `Simulator::set_output_xz`",
+                field
+            );
+            quote! {
+                #[doc = #doc]
+                pub fn #output(&self) -> ::syncrim::common::Input {
+                    ::syncrim::common::Input::new(&self.id, #field)
+                }
+
+                #[doc = #set_doc]
+                pub fn #set(
+                    simulator: &mut ::syncrim::common::Simulator,
+                    value: ::syncrim::common::Signal,
+                ) {
+                    simulator.set_output(#index, value);
+                }
+
+                #[doc = #set_xz_doc]
+                pub fn #set_xz(
+                    simulator: &mut ::syncrim::common::Simulator,
+                    value: ::syncrim::common::Signal,
+                    xz: ::syncrim::common::Signal,
+                ) {
+                    simulator.set_output_xz(#index, value, xz);
+                }
+            }
+        });
+
+    let pos = match has_pos {
+        true => quote! {
+            fn pos(&self) -> ::std::option::Option<(f32, f32)> {
+                ::std::option::Option::Some(self.pos)
+            }
+        },
+        false => quote!(),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::syncrim::common::ComponentPorts for #name #ty_generics #where_clause {
+            fn id_ports(&self) -> (::syncrim::common::Id, ::syncrim::common::Ports) {
+                (
+                    self.id.clone(),
+                    ::syncrim::common::Ports {
+                        inputs: #inputs,
+                        input_widths: #input_widths,
+                        out_type: #out_type,
+                        outputs: ::std::vec![#(::std::string::String::from(#fields)),*],
+                        output_widths: ::std::vec![#(#output_widths),*],
+                    },
+                )
+            }
+
+            #pos
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#accessors)*
+        }
+    })
+}
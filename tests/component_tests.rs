@@ -5,6 +5,8 @@ use syncrim::{
 };
 
 #[test]
+// sums are written out as a + b
+#[allow(clippy::identity_op)]
 fn test_add() {
     let cs = ComponentStore {
         store: vec![
@@ -15,17 +17,27 @@ fn test_add() {
                 pos: (0.0, 0.0),
                 a_in: Input::new("po1", "out"),
                 b_in: Input::new("po2", "out"),
+                width: 32,
+            }),
+            Rc::new(Add {
+                id: "add64".to_string(),
+                pos: (0.0, 0.0),
+                a_in: Input::new("po1", "out"),
+                b_in: Input::new("po2", "out"),
+                width: 64,
             }),
         ],
     };
     let mut clock = 0;
-    let mut simulator = Simulator::new(&cs, &mut clock);
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
 
     assert_eq!(clock, 1);
 
     // outputs
     let add_val = &Input::new("add", "out");
     let add_overflow = &Input::new("add", "overflow");
+    let add64_val = &Input::new("add64", "out");
+    let add64_overflow = &Input::new("add64", "overflow");
 
     // reset
     assert_eq!(simulator.get_input_val(add_val), 0 + 0);
@@ -42,17 +54,32 @@ fn test_add() {
     assert_eq!(simulator.get_input_val(add_val), 42 + 1337);
     assert_eq!(simulator.get_input_val(add_overflow), false as Signal);
 
-    // trigger positive overflow
+    // trigger positive overflow (32 bits)
     println!("<setup for clock 3>");
-    simulator.set_out_val("po1", "out", Signal::MAX / 2);
+    simulator.set_out_val("po1", "out", u32::MAX as Signal / 2);
     simulator.set_out_val("po2", "out", 1);
     println!("sim_state {:?}", simulator.sim_state);
     println!("<clock>");
     simulator.clock(&mut clock);
     println!("sim_state {:?}", simulator.sim_state);
     assert_eq!(clock, 3);
-    assert_eq!(simulator.get_input_val(add_val), Signal::MAX / 2 + 1);
+    assert_eq!(simulator.get_input_val(add_val), u32::MAX as Signal / 2 + 1);
     assert_eq!(simulator.get_input_val(add_overflow), true as Signal);
+    assert_eq!(simulator.get_input_val(add64_overflow), false as Signal);
+
+    // 32 bit wrap around, and positive overflow (64 bits)
+    simulator.set_out_val("po1", "out", Signal::MAX / 2);
+    simulator.set_out_val("po2", "out", Signal::MAX / 2 + 2);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.get_input_val(add_val), 0);
+    assert_eq!(simulator.get_input_val(add_overflow), false as Signal);
+    assert_eq!(simulator.get_input_val(add64_val), 0);
+    assert_eq!(simulator.get_input_val(add64_overflow), false as Signal);
+
+    simulator.set_out_val("po2", "out", 1);
+    simulator.clock(&mut clock);
+    assert_eq!(simulator.get_input_val(add64_val), Signal::MAX / 2 + 1);
+    assert_eq!(simulator.get_input_val(add64_overflow), true as Signal);
 }
 
 #[test]
@@ -74,16 +101,24 @@ fn test_sext() {
                 in_size: 4,
                 out_size: 16,
             }),
+            Rc::new(Sext {
+                id: "sext64".to_string(),
+                pos: (0.0, 0.0),
+                sext_in: Input::new("po", "out"),
+                in_size: 4,
+                out_size: 64,
+            }),
         ],
     };
     let mut clock = 0;
-    let mut simulator = Simulator::new(&cs, &mut clock);
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
 
     assert_eq!(clock, 1);
 
     // outputs
     let sext32_out = &Input::new("sext32", "out");
     let sext16_out = &Input::new("sext16", "out");
+    let sext64_out = &Input::new("sext64", "out");
 
     // reset
     assert_eq!(simulator.get_input_val(sext32_out), 0 as Signal);
@@ -99,6 +134,7 @@ fn test_sext() {
     assert_eq!(clock, 2);
     assert_eq!(simulator.get_input_val(sext32_out), 0xFFFFFFFF as Signal);
     assert_eq!(simulator.get_input_val(sext16_out), 0xFFFF as Signal);
+    assert_eq!(simulator.get_input_val(sext64_out), Signal::MAX);
 
     // Zero-extended
     println!("<setup for clock 3>");
@@ -122,3 +158,100 @@ fn test_sext() {
     assert_eq!(simulator.get_input_val(sext32_out), 0b111 as Signal);
     assert_eq!(simulator.get_input_val(sext16_out), 0b111 as Signal);
 }
+
+#[test]
+fn test_shift_register() {
+    let cs = ComponentStore {
+        store: vec![
+            // registers in reverse order of the data flow
+            Rc::new(Register {
+                id: "r3".to_string(),
+                pos: (0.0, 0.0),
+                r_in: Input::new("r2", "out"),
+            }),
+            Rc::new(Register {
+                id: "r2".to_string(),
+                pos: (0.0, 0.0),
+                r_in: Input::new("r1", "out"),
+            }),
+            Rc::new(Register {
+                id: "r1".to_string(),
+                pos: (0.0, 0.0),
+                r_in: Input::new("po", "out"),
+            }),
+            Rc::new(ProbeOut::new("po")),
+        ],
+    };
+    let mut clock = 0;
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+
+    assert_eq!(clock, 1);
+
+    // outputs
+    let r1_out = &Input::new("r1", "out");
+    let r2_out = &Input::new("r2", "out");
+    let r3_out = &Input::new("r3", "out");
+
+    for value in 1..=5 {
+        simulator.set_out_val("po", "out", value);
+        simulator.clock(&mut clock);
+        println!("sim_state {:?}", simulator.sim_state);
+        assert_eq!(simulator.get_input_val(r1_out), value);
+        assert_eq!(simulator.get_input_val(r2_out), value.saturating_sub(1));
+        assert_eq!(simulator.get_input_val(r3_out), value.saturating_sub(2));
+    }
+}
+
+#[test]
+fn test_register_ring() {
+    let cs = ComponentStore {
+        store: vec![
+            Rc::new(Register {
+                id: "r1".to_string(),
+                pos: (0.0, 0.0),
+                r_in: Input::new("r3", "out"),
+            }),
+            Rc::new(Register {
+                id: "r2".to_string(),
+                pos: (0.0, 0.0),
+                r_in: Input::new("r1", "out"),
+            }),
+            Rc::new(Register {
+                id: "r3".to_string(),
+                pos: (0.0, 0.0),
+                r_in: Input::new("r2", "out"),
+            }),
+        ],
+    };
+    let mut clock = 0;
+    let mut simulator = Simulator::new(&cs, &mut clock).unwrap();
+
+    assert_eq!(clock, 1);
+
+    // outputs
+    let r1_out = &Input::new("r1", "out");
+    let r2_out = &Input::new("r2", "out");
+    let r3_out = &Input::new("r3", "out");
+
+    // initial register contents
+    simulator.set_out_val("r1", "out", 1);
+    simulator.set_out_val("r2", "out", 2);
+    simulator.set_out_val("r3", "out", 3);
+
+    // values rotate one step per clock, regardless of evaluation order
+    for (r1, r2, r3) in [(3, 1, 2), (2, 3, 1), (1, 2, 3), (3, 1, 2)] {
+        simulator.clock(&mut clock);
+        println!("sim_state {:?}", simulator.sim_state);
+        assert_eq!(simulator.get_input_val(r1_out), r1);
+        assert_eq!(simulator.get_input_val(r2_out), r2);
+        assert_eq!(simulator.get_input_val(r3_out), r3);
+    }
+    assert_eq!(clock, 5);
+
+    // stepping back restores the previous rotation
+    simulator.un_clock(&mut clock);
+    assert_eq!(clock, 4);
+    assert_eq!(simulator.get_input_val(r1_out), 1);
+    assert_eq!(simulator.get_input_val(r2_out), 2);
+    assert_eq!(simulator.get_input_val(r3_out), 3);
+}
@@ -0,0 +1,64 @@
+// The models of the examples build without lint errors and simulate, each
+// example included as a module (its `main` is not used)
+use syncrim::{
+    common::{ComponentStore, Simulator},
+    lint::{self, Severity},
+};
+
+#[allow(dead_code)]
+#[path = "../examples/add.rs"]
+mod add;
+#[allow(dead_code)]
+#[path = "../examples/add_edit.rs"]
+mod add_edit;
+#[allow(dead_code)]
+#[path = "../examples/add_mux.rs"]
+mod add_mux;
+#[allow(dead_code)]
+#[path = "../examples/add_reg.rs"]
+mod add_reg;
+#[allow(dead_code)]
+#[path = "../examples/data_mem.rs"]
+mod data_mem;
+#[allow(dead_code)]
+#[path = "../examples/mux.rs"]
+mod mux;
+#[allow(dead_code)]
+#[path = "../examples/probe_edit.rs"]
+mod probe_edit;
+#[allow(dead_code)]
+#[path = "../examples/reg.rs"]
+mod reg;
+#[allow(dead_code)]
+#[path = "../examples/sext.rs"]
+mod sext;
+
+#[test]
+fn test_examples() {
+    let models: Vec<(&str, ComponentStore)> = vec![
+        ("add", add::model()),
+        ("add_edit", add_edit::model()),
+        ("add_mux", add_mux::model()),
+        ("add_reg", add_reg::model()),
+        ("data_mem", data_mem::model()),
+        ("mux", mux::model()),
+        ("probe_edit", probe_edit::model()),
+        ("reg", reg::model()),
+        ("sext", sext::model()),
+    ];
+    for (name, cs) in models {
+        let errors: Vec<String> = lint::check(&cs)
+            .iter()
+            .filter(|p| p.severity() == Severity::Error)
+            .map(|p| p.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{}: {:?}", name, errors);
+
+        let mut clock = 0;
+        let mut simulator =
+            Simulator::new(&cs, &mut clock).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        for _ in 0..8 {
+            simulator.clock(&mut clock);
+        }
+    }
+}